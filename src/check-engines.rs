@@ -1,18 +1,73 @@
-mod finder;
-mod parser;
-mod types;
+use std::process::ExitCode;
 
-fn main() {
-    let package =
-        finder::get_package().expect("Unable to get package.json file in the current directory");
-    let package_lock = finder::get_most_recently_modified_lock()
-        .expect("Unable to get the most recently modified lock file in the current directory");
-    let (parsed_package, _, _) =
-        parser::parse_package(&package).expect("Unable to parse package.json file");
+use anyhow::{anyhow, Context, Result};
+use console::style;
+use riri_node_tools::finder::FindUpOptions;
+use riri_node_tools::{engines, finder, parser, resolver};
 
-    println!("Package content: {parsed_package:?}");
+/// Exit code for errors, such as a missing or unparsable package.json or lock file.
+const EXIT_ERROR: u8 = 2;
+/// Exit code when a locked dependency requires engines the project does not allow.
+const EXIT_CHECK_FAILED: u8 = 1;
 
-    let parsed_lock_package = parser::parse_lock(&package_lock).expect("Unable to parse lock file");
+fn main() -> ExitCode {
+    match run() {
+        Ok(0) => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::from(EXIT_CHECK_FAILED),
+        Err(err) => {
+            eprintln!("{err:#}");
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+/// Prints the engine checks of the project in the current directory, returning the number of
+/// violations.
+fn run() -> Result<usize> {
+    let package = finder::get_package().context("Unable to find package.json")?;
+    let package_lock = finder::get_most_recently_modified_lock(FindUpOptions::default())
+        .context("Unable to find a lock file")?;
+    let (parsed_package, _, _) = parser::parse_package(&package)
+        .map_err(|err| anyhow!("Unable to parse {}: {err}", package.display()))?;
+    let lock = parser::parse_lock(&package_lock, parser::DEFAULT_MAX_LOCK_FILE_SIZE)
+        .map_err(|err| anyhow!("Unable to parse lock file: {err}"))?;
+    let node_version = match finder::get_node_version_file(FindUpOptions::default()) {
+        Some(path) => parser::parse_node_version_file(&path)
+            .map_err(|err| anyhow!("Unable to parse {}: {err}", path.display()))?,
+        None => None,
+    };
+
+    let Some(project_engines) = engines::with_node_version(
+        engines::project_engines(&parsed_package, &lock),
+        node_version,
+    ) else {
+        println!("The project declares no engines to check against");
+        return Ok(0);
+    };
+
+    let resolver = resolver::build_resolver(&lock);
+    let violations: Vec<_> = engines::check_engines(&project_engines, &resolver)
+        .into_iter()
+        .filter(|engine_check| !engine_check.satisfied)
+        .collect();
+    if violations.is_empty() {
+        println!(
+            "All dependency engines are satisfied {}",
+            style(":)").green()
+        );
+        return Ok(0);
+    }
+
+    println!("Dependency engines not satisfied by the project");
+    for violation in &violations {
+        println!(
+            "{} requires {} {}, the project allows {}",
+            style(&violation.dependency).bold(),
+            violation.field,
+            violation.required,
+            violation.project
+        );
+    }
 
-    println!("Lock content: {parsed_lock_package:?}");
+    Ok(violations.len())
 }
@@ -0,0 +1,208 @@
+use crate::resolver::{bun_package_entry, strip_pnpm_peer_suffix, yarn_descriptor_name};
+use crate::types::{
+    NpmDependencies, NpmLock, NpmLockTreeDependency, PackageJson, PackageManagerLock, PnpmLock,
+    VersionedDependencyOrResolved,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+pub type LockedVersions = BTreeMap<String, BTreeSet<String>>;
+
+fn npm_tree_versions(tree: &HashMap<String, NpmLockTreeDependency>, versions: &mut LockedVersions) {
+    for (name, entry) in tree {
+        if let VersionedDependencyOrResolved::Versioned(dependency) = &entry.dependency {
+            versions
+                .entry(name.clone())
+                .or_default()
+                .insert(dependency.version.clone());
+        }
+        npm_tree_versions(&entry.dependencies, versions);
+    }
+}
+
+fn npm_packages_versions(packages: &NpmDependencies, versions: &mut LockedVersions) {
+    for (key, dependency) in packages {
+        let Some((_, name)) = key.rsplit_once("node_modules/") else {
+            continue;
+        };
+        if let VersionedDependencyOrResolved::Versioned(dependency) = dependency {
+            versions
+                .entry(name.to_string())
+                .or_default()
+                .insert(dependency.version.clone());
+        }
+    }
+}
+
+/// Splits a pnpm `packages` key, `/name@1.0.0(peer@1.0.0)` since v6 and `/name/1.0.0_peer@1.0.0`
+/// before, into the package name and its version.
+fn pnpm_package_key(key: &str, is_v5: bool) -> Option<(&str, &str)> {
+    let key = key.trim_start_matches('/');
+
+    if is_v5 {
+        let (name, version) = key.rsplit_once('/')?;
+        return Some((name, version.split_once('_').map_or(version, |(v, _)| v)));
+    }
+
+    let key = strip_pnpm_peer_suffix(key);
+    let separator = key.get(1..)?.find('@')? + 1;
+    Some((&key[..separator], &key[separator + 1..]))
+}
+
+/// Every version a package name is locked at, across the whole dependency tree.
+pub fn locked_versions(lock: &PackageManagerLock) -> LockedVersions {
+    let mut versions = LockedVersions::new();
+
+    match lock {
+        PackageManagerLock::Npm(NpmLock::Version1(lock)) => {
+            npm_tree_versions(&lock.dependencies, &mut versions);
+        }
+        PackageManagerLock::Npm(NpmLock::Version2(lock)) => {
+            if let Some(packages) = &lock.packages {
+                npm_packages_versions(packages, &mut versions);
+            } else {
+                npm_tree_versions(&lock.dependencies, &mut versions);
+            }
+        }
+        PackageManagerLock::Npm(NpmLock::Version3(lock)) => {
+            npm_packages_versions(&lock.packages, &mut versions);
+        }
+        PackageManagerLock::Yarn(lock) => {
+            for (descriptors, dependency) in lock {
+                for name in descriptors.split(',').filter_map(yarn_descriptor_name) {
+                    versions
+                        .entry(name.to_string())
+                        .or_default()
+                        .insert(dependency.version.clone());
+                }
+            }
+        }
+        PackageManagerLock::Pnpm(lock) => {
+            let (packages, is_v5) = match lock {
+                PnpmLock::Version5(lock) => (&lock.packages, true),
+                PnpmLock::Version6(lock) => (&lock.packages, false),
+            };
+            for (name, version) in packages
+                .keys()
+                .filter_map(|key| pnpm_package_key(key, is_v5))
+            {
+                versions
+                    .entry(name.to_string())
+                    .or_default()
+                    .insert(version.to_string());
+            }
+        }
+        PackageManagerLock::Bun(lock) => {
+            for (name, version) in lock
+                .packages
+                .values()
+                .filter_map(|entry| bun_package_entry(entry))
+            {
+                versions
+                    .entry(name.to_string())
+                    .or_default()
+                    .insert(version.to_string());
+            }
+        }
+    }
+
+    versions
+}
+
+/// Direct dependencies of `package_json` locked at more than one version in the tree.
+pub fn find_duplicate_versions(
+    package_json: &PackageJson,
+    lock: &PackageManagerLock,
+) -> LockedVersions {
+    let direct_dependencies: BTreeSet<&String> = [
+        &package_json.dependencies,
+        &package_json.dev_dependencies,
+        &package_json.optional_dependencies,
+    ]
+    .into_iter()
+    .flatten()
+    .flat_map(|dependencies| dependencies.keys())
+    .collect();
+
+    locked_versions(lock)
+        .into_iter()
+        .filter(|(name, versions)| versions.len() > 1 && direct_dependencies.contains(name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{parse_fixture_lock, parse_fixture_package};
+    use crate::types::PackageManager;
+
+    fn duplicates(name: &str, package_manager: PackageManager) -> Vec<(String, Vec<String>)> {
+        find_duplicate_versions(
+            &parse_fixture_package(name),
+            &parse_fixture_lock(name, package_manager),
+        )
+        .into_iter()
+        .map(|(name, versions)| (name, versions.into_iter().collect()))
+        .collect()
+    }
+
+    #[test]
+    fn npm_duplicate_versions() {
+        assert_eq!(
+            duplicates("npm-duplicate-versions", PackageManager::Npm),
+            vec![(
+                "lodash".to_string(),
+                vec!["3.10.1".to_string(), "4.17.21".to_string()]
+            )]
+        );
+        assert!(duplicates("npm-sections", PackageManager::Npm).is_empty());
+        assert_eq!(
+            duplicates("npm-v1-nested", PackageManager::Npm),
+            vec![(
+                "lodash".to_string(),
+                vec!["3.10.1".to_string(), "4.17.21".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn pnpm_duplicate_versions() {
+        assert_eq!(
+            duplicates("pnpm-duplicate-versions", PackageManager::Pnpm),
+            vec![(
+                "@types/node".to_string(),
+                vec!["18.19.3".to_string(), "20.10.5".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn bun_duplicate_versions() {
+        assert_eq!(
+            duplicates("bun-text-lock", PackageManager::Bun),
+            vec![(
+                "@types/node".to_string(),
+                vec!["18.19.3".to_string(), "20.10.5".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn pnpm_package_keys() {
+        assert_eq!(
+            pnpm_package_key("/react-dom@18.2.0(react@18.2.0)", false),
+            Some(("react-dom", "18.2.0"))
+        );
+        assert_eq!(
+            pnpm_package_key("/@types/node@20.10.5", false),
+            Some(("@types/node", "20.10.5"))
+        );
+        assert_eq!(
+            pnpm_package_key("/react-dom/18.2.0_react@18.2.0", true),
+            Some(("react-dom", "18.2.0"))
+        );
+        assert_eq!(
+            pnpm_package_key("/@types/node/20.10.5", true),
+            Some(("@types/node", "20.10.5"))
+        );
+    }
+}
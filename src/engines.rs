@@ -0,0 +1,249 @@
+use crate::resolver::{convert_npm_engines_to_object_engines, DependencyVersionResolver};
+use crate::types::{Engine, ObjectEngines, PackageJson, PackageManagerLock};
+use semver::{Op, Version, VersionReq};
+use serde::Serialize;
+
+/// An engine range required by a locked dependency, compared with the project's own range.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EngineCheck {
+    pub dependency: String,
+    pub field: Engine,
+    pub required: String,
+    pub project: String,
+    pub satisfied: bool,
+}
+
+/// Engines declared by the project: package.json `engines`, falling back to the npm lock root.
+pub fn project_engines(
+    package_json: &PackageJson,
+    lock: &PackageManagerLock,
+) -> Option<ObjectEngines> {
+    let engines = match (&package_json.engines, lock) {
+        (Some(engines), _) => Some(engines.clone()),
+        (None, PackageManagerLock::Npm(npm_lock)) => npm_lock.root_engines().cloned(),
+        (None, _) => None,
+    };
+
+    convert_npm_engines_to_object_engines(engines)
+}
+
+/// Uses the version of a `.nvmrc`, `.node-version` or `.tool-versions` file as the project's Node
+/// range when `engines` does not declare one.
+pub fn with_node_version(
+    engines: Option<ObjectEngines>,
+    node_version: Option<String>,
+) -> Option<ObjectEngines> {
+    let Some(node_version) = node_version else {
+        return engines;
+    };
+
+    let mut engines = engines.unwrap_or_default();
+    engines.entry(Engine::Node).or_insert(node_version);
+    Some(engines)
+}
+
+/// Converts one npm range, such as `>= 16.14 <19 || ^20`, into its `||` alternatives.
+fn parse_npm_range(range: &str) -> Option<Vec<VersionReq>> {
+    range
+        .split("||")
+        .map(|alternative| {
+            let mut comparators: Vec<String> = Vec::new();
+            let mut operator = String::new();
+            for token in alternative.split_whitespace() {
+                if token
+                    .chars()
+                    .all(|c| matches!(c, '<' | '>' | '=' | '^' | '~'))
+                {
+                    operator.push_str(token);
+                } else {
+                    comparators.push(format!("{operator}{}", token.trim_start_matches('v')));
+                    operator.clear();
+                }
+            }
+
+            match comparators.as_slice() {
+                [] => Some(VersionReq::STAR),
+                [from, hyphen, to] if hyphen == "-" => {
+                    VersionReq::parse(&format!(">={from}, <={to}")).ok()
+                }
+                _ => VersionReq::parse(&comparators.join(", ")).ok(),
+            }
+        })
+        .collect()
+}
+
+/// Lowest version allowed by `req`, `0.0.0` when it has no lower bound.
+fn lowest_version(req: &VersionReq) -> Version {
+    req.comparators
+        .iter()
+        .filter_map(|comparator| {
+            let minor = comparator.minor.unwrap_or(0);
+            let patch = comparator.patch.unwrap_or(0);
+            let mut version = match comparator.op {
+                Op::Greater => match (comparator.minor, comparator.patch) {
+                    (Some(_), Some(patch)) => Version::new(comparator.major, minor, patch + 1),
+                    (Some(minor), None) => Version::new(comparator.major, minor + 1, 0),
+                    _ => Version::new(comparator.major + 1, 0, 0),
+                },
+                Op::Less | Op::LessEq => return None,
+                _ => Version::new(comparator.major, minor, patch),
+            };
+            version.pre = comparator.pre.clone();
+            Some(version)
+        })
+        .max()
+        .unwrap_or_else(|| Version::new(0, 0, 0))
+}
+
+/// Lowest version an npm range allows, `None` when it cannot be parsed.
+pub(crate) fn lowest_allowed_version(range: &str) -> Option<Version> {
+    parse_npm_range(range)?.iter().map(lowest_version).min()
+}
+
+/// Whether `version` is allowed by the npm range `range`, `None` when either cannot be parsed.
+pub(crate) fn version_satisfies(version: &str, range: &str) -> Option<bool> {
+    let version = Version::parse(version.trim_start_matches('v')).ok()?;
+
+    Some(
+        parse_npm_range(range)?
+            .iter()
+            .any(|req| req.matches(&version)),
+    )
+}
+
+/// Whether the lowest version of every `project` alternative satisfies `required`, `None` when
+/// either range cannot be parsed.
+pub fn range_satisfies(project: &str, required: &str) -> Option<bool> {
+    let project = parse_npm_range(project)?;
+    let required = parse_npm_range(required)?;
+
+    Some(project.iter().all(|project| {
+        let lowest = lowest_version(project);
+        required.iter().any(|required| required.matches(&lowest))
+    }))
+}
+
+/// Compares the engines of every locked dependency with `project_engines`, sorted by dependency.
+/// Engines the project does not declare, engines other than node, npm and yarn, or ranges that
+/// cannot be parsed, are not checked.
+pub fn check_engines(
+    project_engines: &ObjectEngines,
+    resolver: &DependencyVersionResolver,
+) -> Vec<EngineCheck> {
+    let mut checks: Vec<EngineCheck> = resolver
+        .locked_dependencies
+        .iter()
+        .flat_map(|(key, dependency)| {
+            let name = key
+                .rsplit_once("node_modules/")
+                .map_or(key.as_str(), |(_, name)| name);
+            dependency
+                .engines
+                .iter()
+                .flatten()
+                .filter(|(field, _)| !matches!(field, Engine::Other(_)))
+                .filter_map(move |(field, required)| {
+                    let project = project_engines.get(field)?;
+                    Some(EngineCheck {
+                        dependency: name.to_string(),
+                        field: field.clone(),
+                        required: required.clone(),
+                        project: project.clone(),
+                        satisfied: range_satisfies(project, required)?,
+                    })
+                })
+        })
+        .collect();
+    checks.sort_by(|a, b| (&a.dependency, &a.field).cmp(&(&b.dependency, &b.field)));
+
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolver::{build_resolver, LockDependencies};
+    use crate::test_utils::{parse_fixture_lock, parse_fixture_package};
+    use crate::types::{LockDependency, PackageManager};
+
+    #[test]
+    fn project_range_satisfies_required_range() {
+        assert_eq!(range_satisfies(">=18", ">=4"), Some(true));
+        assert_eq!(range_satisfies(">=16", ">=18"), Some(false));
+        assert_eq!(
+            range_satisfies(">= 18.17 <21", "^18.17.0 || >=20.5.0"),
+            Some(true)
+        );
+        assert_eq!(range_satisfies("^16 || ^18", ">=18"), Some(false));
+        assert_eq!(range_satisfies("18 - 20", ">=18.0.0"), Some(true));
+        assert_eq!(range_satisfies(">17", ">=18"), Some(true));
+        assert_eq!(range_satisfies("latest", ">=18"), None);
+    }
+
+    #[test]
+    fn node_version_file_fills_missing_node_engine() {
+        let declared = ObjectEngines::from([(Engine::Node, ">=18".to_string())]);
+
+        assert_eq!(
+            with_node_version(Some(declared.clone()), Some("16".to_string())),
+            Some(declared)
+        );
+        assert_eq!(
+            with_node_version(None, Some("16".to_string())),
+            Some(ObjectEngines::from([(Engine::Node, "16".to_string())]))
+        );
+        assert_eq!(with_node_version(None, None), None);
+    }
+
+    #[test]
+    fn check_engines_reports_mismatches() {
+        let name = "npm-engines-mismatch";
+        let lock = parse_fixture_lock(name, PackageManager::Npm);
+        let project_engines =
+            project_engines(&parse_fixture_package(name), &lock).expect("Expected project engines");
+
+        let checks = check_engines(&project_engines, &build_resolver(&lock));
+
+        assert_eq!(
+            checks,
+            vec![
+                EngineCheck {
+                    dependency: "lodash".to_string(),
+                    field: Engine::Node,
+                    required: ">=4".to_string(),
+                    project: ">=16".to_string(),
+                    satisfied: true,
+                },
+                EngineCheck {
+                    dependency: "modern-lib".to_string(),
+                    field: Engine::Node,
+                    required: ">=18".to_string(),
+                    project: ">=16".to_string(),
+                    satisfied: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn check_engines_skips_other_engines() {
+        let project_engines =
+            ObjectEngines::from([(Engine::Other("pnpm".to_string()), ">=8".to_string())]);
+        let resolver = DependencyVersionResolver {
+            locked_dependencies: LockDependencies::from([(
+                "node_modules/extension".to_string(),
+                LockDependency {
+                    version: "1.0.0".to_string(),
+                    engines: Some(ObjectEngines::from([
+                        (Engine::Other("vscode".to_string()), "^1.80.0".to_string()),
+                        (Engine::Other("pnpm".to_string()), ">=9".to_string()),
+                    ])),
+                },
+            )]),
+            resolve_dependency_key: |name, _| name.to_string(),
+            lockfile_version: None,
+        };
+
+        assert!(check_engines(&project_engines, &resolver).is_empty());
+    }
+}
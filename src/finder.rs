@@ -1,68 +1,146 @@
-use crate::types::{LockFileResult, PackageManager};
+use crate::parser::STDIN_PATH;
+use crate::types::{
+    LockFileResult, PackageManager, BUN_LOCK_FILE, NPM_LOCK_FILE, PNPM_LOCK_FILE, YARN_LOCK_FILE,
+};
 use anyhow::Result;
 use std::io::{Error, ErrorKind};
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
+use tracing::debug;
 
-fn find_up_multiple<T: AsRef<Path>>(file_names: &[T]) -> Result<Vec<PathBuf>> {
-    let cwd = std::env::current_dir().expect("Failed to get the current directory!");
-    let mut matches = Vec::new();
-    let mut target_dir = Some(cwd);
-    while let Some(dir) = target_dir.clone() {
-        for entry in std::fs::read_dir(&dir)? {
-            let entry = entry?;
-            let path = entry.path();
-
-            for target_file_name in file_names {
-                if let Some(file_name) = path.file_name() {
-                    if target_file_name.as_ref() == file_name {
-                        matches.push(path.clone());
-                    }
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FindUpOptions {
+    /// Number of parent directories visited above the start directory, unlimited if unset.
+    pub max_depth: Option<usize>,
+    /// Do not walk above the first directory containing a `.git` entry.
+    pub stop_at_git_root: bool,
+}
+
+/// Lazily yields `start` and each of its parents, up to the root or the boundary set by
+/// [`FindUpOptions`].
+#[derive(Debug)]
+pub struct AncestorDirs {
+    next: Option<PathBuf>,
+    depth: usize,
+    options: FindUpOptions,
+}
+
+impl AncestorDirs {
+    pub fn new(start: &Path, options: FindUpOptions) -> Self {
+        AncestorDirs {
+            next: Some(start.to_path_buf()),
+            depth: 0,
+            options,
+        }
+    }
+}
+
+impl Iterator for AncestorDirs {
+    type Item = PathBuf;
+
+    fn next(&mut self) -> Option<PathBuf> {
+        let dir = self.next.take()?;
+
+        if self.options.stop_at_git_root && dir.join(".git").exists() {
+            debug!("Reached the git repository root at {}.", dir.display());
+        } else if self
+            .options
+            .max_depth
+            .is_some_and(|max_depth| self.depth >= max_depth)
+        {
+            debug!("Reached the maximum find-up depth at {}.", dir.display());
+        } else {
+            self.depth += 1;
+            self.next = dir.parent().map(Path::to_path_buf);
+        }
+
+        Some(dir)
+    }
+}
+
+fn find_up_multiple_from<T: AsRef<Path>>(
+    start: &Path,
+    file_names: &[T],
+    options: FindUpOptions,
+) -> Vec<PathBuf> {
+    for dir in AncestorDirs::new(start, options) {
+        match std::fs::read_dir(&dir) {
+            Ok(entries) => {
+                let matches: Vec<PathBuf> = entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|path| {
+                        path.file_name().is_some_and(|file_name| {
+                            file_names
+                                .iter()
+                                .any(|target_file_name| target_file_name.as_ref() == file_name)
+                        })
+                    })
+                    .collect();
+
+                if !matches.is_empty() {
+                    return matches;
                 }
             }
-
-            if !matches.is_empty() {
-                return Ok(matches);
+            Err(err) => {
+                debug!("Unable to read directory {}: {}.", dir.display(), err);
             }
-
-            target_dir = dir.parent().map(Path::to_path_buf);
         }
     }
-    Ok(matches)
+
+    Vec::new()
 }
 
-fn get_file_path(path_str: &str) -> Result<PathBuf, Error> {
-    let path = Path::new(path_str);
+fn find_up_multiple<T: AsRef<Path>>(
+    file_names: &[T],
+    options: FindUpOptions,
+) -> Result<Vec<PathBuf>> {
+    let cwd = std::env::current_dir()?;
+    Ok(find_up_multiple_from(&cwd, file_names, options))
+}
+
+fn get_file_path<P: AsRef<Path>>(path: P) -> Result<PathBuf, Error> {
+    let path = path.as_ref();
     if !path.exists() {
         return Err(Error::new(
             ErrorKind::NotFound,
-            format!("{path_str:?} file not found!"),
+            format!("{} file not found!", path.display()),
         ));
     }
 
     Ok(path.to_path_buf())
 }
 
-fn find_most_recently_modified(files: &Vec<PathBuf>) -> Option<PathBuf> {
-    if files.is_empty() {
-        return None;
+fn modified_time(file: &Path) -> Option<SystemTime> {
+    let resolved_file = file.canonicalize().ok()?;
+    resolved_file.metadata().ok()?.modified().ok()
+}
+
+/// Whether `lock` was last modified before `package`, hinting that package.json was edited
+/// without reinstalling. Unknown modification times are not reported as stale.
+pub fn is_lock_older_than_package(package: &Path, lock: &Path) -> bool {
+    match (modified_time(package), modified_time(lock)) {
+        (Some(package_time), Some(lock_time)) => lock_time < package_time,
+        _ => false,
     }
+}
 
-    let mut most_recent_file = files[0].clone();
-    let mut most_recent_time = SystemTime::UNIX_EPOCH;
+fn find_most_recently_modified(files: &[PathBuf]) -> Option<PathBuf> {
+    let mut most_recent: Option<(&PathBuf, SystemTime)> = None;
 
     for file in files {
-        if let Ok(metadata) = file.metadata() {
-            if let Ok(modified_time) = metadata.modified() {
-                if modified_time > most_recent_time {
-                    most_recent_time = modified_time;
-                    most_recent_file.clone_from(file);
-                }
+        if let Some(modified_time) = modified_time(file) {
+            match most_recent {
+                Some((_, most_recent_time)) if modified_time <= most_recent_time => {}
+                _ => most_recent = Some((file, modified_time)),
             }
         }
     }
 
-    Some(most_recent_file)
+    most_recent
+        .map(|(file, _)| file)
+        .or_else(|| files.first())
+        .cloned()
 }
 
 pub fn get_package() -> Result<PathBuf, Error> {
@@ -72,31 +150,96 @@ pub fn get_package() -> Result<PathBuf, Error> {
     }
 }
 
-const NPM_LOCK_FILE: &str = "package-lock.json";
-const YARN_LOCK_FILE: &str = "yarn.lock";
-const PNPM_LOCK_FILE: &str = "pnpm-lock.yaml";
-
-pub fn get_most_recently_modified_lock() -> Result<LockFileResult, Error> {
-    let lock_file_names = vec![NPM_LOCK_FILE, YARN_LOCK_FILE, PNPM_LOCK_FILE];
-    if let Ok(matches) = find_up_multiple(&lock_file_names) {
-        if let Some(most_recent_file) = find_most_recently_modified(&matches) {
-            let package_manager = match most_recent_file.file_name().and_then(|s| s.to_str()) {
-                Some(NPM_LOCK_FILE) => PackageManager::Npm,
-                Some(YARN_LOCK_FILE) => PackageManager::Yarn,
-                Some(PNPM_LOCK_FILE) => PackageManager::Pnpm,
-                _ => {
-                    return Err(Error::new(
-                        ErrorKind::InvalidData,
-                        "Unknown lock file format",
-                    ));
-                }
-            };
+pub fn get_manifest(path: &Path) -> Result<PathBuf, Error> {
+    if path.as_os_str() == STDIN_PATH {
+        return Ok(path.to_path_buf());
+    }
 
-            return Ok(LockFileResult {
-                path: most_recent_file,
-                package_manager,
-            });
-        }
+    let path = get_file_path(path)?;
+    if !path.is_file() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} is not a file!", path.display()),
+        ));
+    }
+
+    Ok(path)
+}
+
+const NPMRC_FILE: &str = ".npmrc";
+
+pub fn get_npmrc(options: FindUpOptions) -> Option<PathBuf> {
+    find_up_multiple(&[NPMRC_FILE], options)
+        .ok()
+        .and_then(|matches| matches.into_iter().next())
+}
+
+const NPDRC_FILE: &str = ".npdrc";
+
+pub fn get_npdrc(options: FindUpOptions) -> Option<PathBuf> {
+    find_up_multiple(&[NPDRC_FILE], options)
+        .ok()
+        .and_then(|matches| matches.into_iter().next())
+}
+
+const NVMRC_FILE: &str = ".nvmrc";
+const NODE_VERSION_FILE: &str = ".node-version";
+pub const TOOL_VERSIONS_FILE: &str = ".tool-versions";
+
+/// Closest `.nvmrc`, `.node-version` or asdf `.tool-versions` file, in that order of preference
+/// when a directory has several.
+pub fn get_node_version_file(options: FindUpOptions) -> Option<PathBuf> {
+    let file_names = [NVMRC_FILE, NODE_VERSION_FILE, TOOL_VERSIONS_FILE];
+
+    find_up_multiple(&file_names, options)
+        .ok()
+        .and_then(|matches| {
+            matches.into_iter().min_by_key(|path| {
+                file_names
+                    .iter()
+                    .position(|file_name| path.ends_with(file_name))
+            })
+        })
+}
+
+const YARNRC_FILE: &str = ".yarnrc.yml";
+
+pub fn get_yarnrc(options: FindUpOptions) -> Option<PathBuf> {
+    find_up_multiple(&[YARNRC_FILE], options)
+        .ok()
+        .and_then(|matches| matches.into_iter().next())
+}
+
+fn package_manager_for_lock(path: &Path) -> Option<PackageManager> {
+    path.file_name()
+        .and_then(|s| s.to_str())
+        .and_then(PackageManager::from_lock_filename)
+}
+
+pub fn get_most_recently_modified_lock(options: FindUpOptions) -> Result<LockFileResult, Error> {
+    get_most_recently_modified_lock_from(&std::env::current_dir()?, options)
+}
+
+/// Same as [`get_most_recently_modified_lock`], walking up from `start` instead of the current
+/// directory.
+pub fn get_most_recently_modified_lock_from(
+    start: &Path,
+    options: FindUpOptions,
+) -> Result<LockFileResult, Error> {
+    let lock_file_names = vec![NPM_LOCK_FILE, YARN_LOCK_FILE, PNPM_LOCK_FILE, BUN_LOCK_FILE];
+    let matches = find_up_multiple_from(start, &lock_file_names, options);
+    if let Some(most_recent_file) = find_most_recently_modified(&matches) {
+        let Some(package_manager) = package_manager_for_lock(&most_recent_file) else {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Unknown lock file format",
+            ));
+        };
+
+        return Ok(LockFileResult {
+            path: most_recent_file,
+            package_manager,
+        });
     }
 
     Err(Error::new(
@@ -104,3 +247,303 @@ pub fn get_most_recently_modified_lock() -> Result<LockFileResult, Error> {
         "Unable to find any lock file inside the current directory!",
     ))
 }
+
+pub fn get_lock(
+    path: &Path,
+    package_manager: Option<PackageManager>,
+) -> Result<LockFileResult, Error> {
+    if path.as_os_str() != STDIN_PATH && !path.is_file() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("{} lock file not found!", path.display()),
+        ));
+    }
+
+    let Some(package_manager) = package_manager.or_else(|| package_manager_for_lock(path)) else {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Unable to infer the package manager of {}, it has to be given explicitly",
+                path.display()
+            ),
+        ));
+    };
+
+    Ok(LockFileResult {
+        path: path.to_path_buf(),
+        package_manager,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+    use std::fs::File;
+    use std::time::Duration;
+
+    fn touch(path: &Path, modified: SystemTime) {
+        File::create(path)
+            .and_then(|file| file.set_modified(modified))
+            .expect("Unable to create file");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn most_recently_modified_follows_symlinks() {
+        let dir = temp_dir("most-recently-modified-symlink");
+        let now = SystemTime::now();
+        touch(
+            &dir.join(NPM_LOCK_FILE),
+            now.checked_sub(Duration::from_secs(100))
+                .expect("Invalid time"),
+        );
+        touch(&dir.join("pnpm-lock.target.yaml"), now);
+        std::os::unix::fs::symlink(dir.join("pnpm-lock.target.yaml"), dir.join(PNPM_LOCK_FILE))
+            .expect("Unable to create symlink");
+
+        let files = vec![dir.join(NPM_LOCK_FILE), dir.join(PNPM_LOCK_FILE)];
+
+        assert_eq!(
+            find_most_recently_modified(&files),
+            Some(dir.join(PNPM_LOCK_FILE))
+        );
+    }
+
+    #[test]
+    fn find_up_skips_missing_directories() {
+        let dir = temp_dir("find-up-missing");
+        touch(&dir.join(PNPM_LOCK_FILE), SystemTime::now());
+        let missing_dir = dir.join("missing").join("nested");
+
+        assert_eq!(
+            find_up_multiple_from(&missing_dir, &[PNPM_LOCK_FILE], FindUpOptions::default()),
+            vec![dir.join(PNPM_LOCK_FILE)]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn find_up_skips_unreadable_directories() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("find-up-unreadable");
+        touch(&dir.join(PNPM_LOCK_FILE), SystemTime::now());
+        let unreadable_dir = dir.join("unreadable");
+        std::fs::create_dir_all(&unreadable_dir).expect("Unable to create directory");
+        touch(&unreadable_dir.join(NPM_LOCK_FILE), SystemTime::now());
+        std::fs::set_permissions(&unreadable_dir, std::fs::Permissions::from_mode(0o000))
+            .expect("Unable to change permissions");
+
+        // Permissions are not enforced for root, the directory stays readable then.
+        let is_unreadable = std::fs::read_dir(&unreadable_dir).is_err();
+        let found = find_up_multiple_from(
+            &unreadable_dir,
+            &[NPM_LOCK_FILE, PNPM_LOCK_FILE],
+            FindUpOptions::default(),
+        );
+        std::fs::set_permissions(&unreadable_dir, std::fs::Permissions::from_mode(0o755))
+            .expect("Unable to restore permissions");
+
+        if is_unreadable {
+            assert_eq!(found, vec![dir.join(PNPM_LOCK_FILE)]);
+        } else {
+            assert_eq!(found, vec![unreadable_dir.join(NPM_LOCK_FILE)]);
+        }
+    }
+
+    #[test]
+    fn most_recently_modified_falls_back_to_first_file() {
+        let dir = temp_dir("most-recently-modified-missing");
+        let files = vec![dir.join(YARN_LOCK_FILE), dir.join(NPM_LOCK_FILE)];
+
+        assert_eq!(
+            find_most_recently_modified(&files),
+            Some(dir.join(YARN_LOCK_FILE))
+        );
+        assert_eq!(find_most_recently_modified(&[]), None);
+    }
+
+    #[test]
+    fn explicit_lock_file() {
+        let dir = temp_dir("explicit-lock");
+        let renamed_lock = dir.join("npm.lock.json");
+        touch(&renamed_lock, SystemTime::now());
+        touch(&dir.join(YARN_LOCK_FILE), SystemTime::now());
+
+        let lock = get_lock(&renamed_lock, Some(PackageManager::Npm)).expect("Expected a lock");
+        assert_eq!(lock.package_manager, PackageManager::Npm);
+        assert_eq!(lock.path, renamed_lock);
+
+        let lock = get_lock(&dir.join(YARN_LOCK_FILE), None).expect("Expected a lock");
+        assert_eq!(lock.package_manager, PackageManager::Yarn);
+
+        assert_eq!(
+            get_lock(&renamed_lock, None)
+                .map_err(|err| err.kind())
+                .err(),
+            Some(ErrorKind::InvalidInput)
+        );
+        assert_eq!(
+            get_lock(&dir.join("missing.json"), Some(PackageManager::Npm))
+                .map_err(|err| err.kind())
+                .err(),
+            Some(ErrorKind::NotFound)
+        );
+    }
+
+    #[test]
+    fn find_up_stops_at_max_depth() {
+        let dir = temp_dir("find-up-max-depth");
+        touch(&dir.join(NPM_LOCK_FILE), SystemTime::now());
+        let nested_dir = dir.join("a").join("b");
+        std::fs::create_dir_all(&nested_dir).expect("Unable to create nested directories");
+
+        let find_up = |max_depth| {
+            find_up_multiple_from(
+                &nested_dir,
+                &[NPM_LOCK_FILE],
+                FindUpOptions {
+                    max_depth,
+                    ..FindUpOptions::default()
+                },
+            )
+        };
+
+        assert!(find_up(Some(0)).is_empty());
+        assert!(find_up(Some(1)).is_empty());
+        assert_eq!(find_up(Some(2)), vec![dir.join(NPM_LOCK_FILE)]);
+        assert_eq!(find_up(None), vec![dir.join(NPM_LOCK_FILE)]);
+    }
+
+    #[test]
+    fn find_up_stops_at_git_root() {
+        let dir = temp_dir("find-up-git-root");
+        touch(&dir.join("package.json"), SystemTime::now());
+        let repository_dir = dir.join("repository");
+        std::fs::create_dir_all(repository_dir.join(".git"))
+            .expect("Unable to create git directory");
+        let nested_dir = repository_dir.join("packages").join("app");
+        std::fs::create_dir_all(&nested_dir).expect("Unable to create nested directories");
+
+        let find_up = |stop_at_git_root| {
+            find_up_multiple_from(
+                &nested_dir,
+                &["package.json"],
+                FindUpOptions {
+                    stop_at_git_root,
+                    ..FindUpOptions::default()
+                },
+            )
+        };
+
+        assert!(find_up(true).is_empty());
+        assert_eq!(find_up(false), vec![dir.join("package.json")]);
+    }
+
+    #[test]
+    fn ancestor_dirs_boundaries() {
+        let start = Path::new("/a/b/c");
+        let ancestors = |options| AncestorDirs::new(start, options).collect::<Vec<_>>();
+
+        assert_eq!(
+            ancestors(FindUpOptions::default()),
+            ["/a/b/c", "/a/b", "/a", "/"].map(PathBuf::from)
+        );
+        assert_eq!(
+            ancestors(FindUpOptions {
+                max_depth: Some(0),
+                ..FindUpOptions::default()
+            }),
+            [PathBuf::from("/a/b/c")]
+        );
+        assert_eq!(
+            ancestors(FindUpOptions {
+                max_depth: Some(2),
+                ..FindUpOptions::default()
+            }),
+            ["/a/b/c", "/a/b", "/a"].map(PathBuf::from)
+        );
+        assert_eq!(
+            ancestors(FindUpOptions {
+                max_depth: Some(10),
+                ..FindUpOptions::default()
+            }),
+            ["/a/b/c", "/a/b", "/a", "/"].map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn ancestor_dirs_include_git_root() {
+        let dir = temp_dir("ancestor-dirs-git-root");
+        std::fs::create_dir_all(dir.join(".git")).expect("Unable to create git directory");
+        let nested_dir = dir.join("nested");
+
+        assert_eq!(
+            AncestorDirs::new(
+                &nested_dir,
+                FindUpOptions {
+                    stop_at_git_root: true,
+                    ..FindUpOptions::default()
+                }
+            )
+            .collect::<Vec<_>>(),
+            vec![nested_dir.clone(), dir]
+        );
+    }
+
+    #[test]
+    fn file_not_found_message_displays_path() {
+        let err = get_file_path(r"missing/dir\package.json").expect_err("Expected a missing file");
+
+        assert_eq!(err.to_string(), r"missing/dir\package.json file not found!");
+    }
+
+    #[test]
+    fn explicit_manifest_must_be_a_file() {
+        let dir = temp_dir("explicit-manifest");
+        let manifest = dir.join("app.package.json");
+        std::fs::write(&manifest, "{}").expect("Unable to write manifest");
+
+        assert_eq!(
+            get_manifest(&manifest).expect("Expected a manifest"),
+            manifest
+        );
+        assert_eq!(
+            get_manifest(&dir)
+                .expect_err("Expected a directory to be rejected")
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            get_manifest(&dir.join("missing.json"))
+                .expect_err("Expected a missing manifest")
+                .kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn lock_older_than_package() {
+        let dir = temp_dir("lock-older-than-package");
+        let now = SystemTime::now();
+        let earlier = now
+            .checked_sub(Duration::from_secs(100))
+            .expect("Invalid time");
+        let package = dir.join("package.json");
+        let lock = dir.join(NPM_LOCK_FILE);
+
+        touch(&package, now);
+        touch(&lock, earlier);
+        assert!(is_lock_older_than_package(&package, &lock));
+
+        touch(&package, earlier);
+        touch(&lock, now);
+        assert!(!is_lock_older_than_package(&package, &lock));
+
+        assert!(!is_lock_older_than_package(
+            &package,
+            &dir.join(YARN_LOCK_FILE)
+        ));
+    }
+}
@@ -0,0 +1,83 @@
+use crate::types::PackageJson;
+use anyhow::{anyhow, bail, Result};
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::process::Command;
+use tracing::debug;
+
+/// Standard output of `git` run in `dir`, `None` when it cannot run or fails.
+fn git(dir: &Path, args: &[&str]) -> Option<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+
+    output.status.success().then_some(output.stdout)
+}
+
+/// Dependencies of `package_json` added or given another specifier since `git_ref`, compared
+/// section by section with the file at `package` as committed in `git_ref`. `None` when `package`
+/// is not inside a git work tree.
+pub fn changed_dependencies_since(
+    package: &Path,
+    package_json: &PackageJson,
+    git_ref: &str,
+) -> Result<Option<BTreeSet<String>>> {
+    let dir = match package.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if git(dir, &["rev-parse", "--is-inside-work-tree"]).is_none() {
+        return Ok(None);
+    }
+    if git(
+        dir,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{git_ref}^{{commit}}"),
+        ],
+    )
+    .is_none()
+    {
+        bail!("Unknown git ref {git_ref}");
+    }
+
+    let file_name = package
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .ok_or_else(|| anyhow!("Invalid package.json path {}", package.display()))?;
+    let previous = git(dir, &["show", &format!("{git_ref}:./{file_name}")])
+        .map(|contents| serde_json::from_slice::<PackageJson>(&contents))
+        .transpose()
+        .map_err(|err| anyhow!("Unable to parse {file_name} as of {git_ref}: {err}"))?;
+    if previous.is_none() {
+        debug!("{} does not exist in {}.", file_name, git_ref);
+    }
+    let previous_sections = previous.as_ref().map(PackageJson::dependency_sections);
+
+    let mut changed = BTreeSet::new();
+    for (index, (section, dependencies)) in
+        package_json.dependency_sections().into_iter().enumerate()
+    {
+        let previous_dependencies = previous_sections
+            .as_ref()
+            .and_then(|sections| sections[index].1);
+        for (name, specifier) in dependencies.into_iter().flatten() {
+            if previous_dependencies.and_then(|dependencies| dependencies.get(name))
+                != Some(specifier)
+            {
+                debug!(
+                    "Dependency {} changed in {} since {}.",
+                    name, section, git_ref
+                );
+                changed.insert(name.clone());
+            }
+        }
+    }
+
+    Ok(Some(changed))
+}
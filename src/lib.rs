@@ -0,0 +1,18 @@
+#![allow(
+    clippy::missing_errors_doc,
+    clippy::missing_panics_doc,
+    clippy::must_use_candidate
+)]
+
+pub mod dedupe;
+pub mod engines;
+pub mod finder;
+pub mod git;
+pub mod parser;
+pub mod pin;
+pub mod registry;
+pub mod resolver;
+#[cfg(test)]
+mod test_utils;
+pub mod types;
+pub mod workspace;
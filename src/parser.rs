@@ -1,64 +1,264 @@
+use crate::finder::TOOL_VERSIONS_FILE;
 use crate::types::{
-    LockFileResult, NpmLock, PackageJson, PackageManager, PackageManagerLock, PnpmLock, YarnLockV2,
+    BunLock, DenoJson, Dependencies, DependencySpecifier, LockFileResult, NpdConfig, NpmLock,
+    Npmrc, PackageJson, PackageManager, PackageManagerLock, PnpmLock, YarnLockV2, YarnLockVersion,
+    Yarnrc,
 };
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use detect_indent::{detect_indent, Indent};
 use regex::Regex;
+use serde::Deserialize;
 use serde_json::{Value as JsonValue, Value};
 use serde_yml::Value as YamlValue;
 use std::error::Error;
+use std::fmt;
 use std::fs::File;
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tracing::{debug, warn};
 
-pub fn parse_package(path: &PathBuf) -> Result<(PackageJson, Value, Indent), Box<dyn Error>> {
-    let mut file = File::open(path)?;
+/// Path standing for the standard input, e.g. `--lockfile -`.
+pub const STDIN_PATH: &str = "-";
+
+/// The standard input can only be consumed once, keep it for every later read.
+fn read_stdin() -> Result<String> {
+    static STDIN: OnceLock<String> = OnceLock::new();
 
+    if let Some(contents) = STDIN.get() {
+        return Ok(contents.clone());
+    }
     let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+    std::io::stdin()
+        .read_to_string(&mut contents)
+        .map_err(|err| anyhow!("failed to read the standard input: {err}"))?;
+    Ok(STDIN.get_or_init(|| contents).clone())
+}
+
+fn read_file(path: &PathBuf) -> Result<String> {
+    if path.as_os_str() == STDIN_PATH {
+        return read_stdin();
+    }
+
+    // The io error is kept in the message, callers printing `{err}` only see the outermost one.
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|err| anyhow!("failed to open {}: {err}", path.display()))?;
+
+    Ok(contents)
+}
+
+pub fn parse_package(path: &PathBuf) -> Result<(PackageJson, Value, Indent), Box<dyn Error>> {
+    parse_package_content(&read_file(path)?)
+}
+
+/// Parses package.json `contents` once into a [`Value`], the typed view is deserialized from it.
+fn parse_package_content(contents: &str) -> Result<(PackageJson, Value, Indent), Box<dyn Error>> {
+    let indent = detect_indent(contents);
+    let raw: Value = serde_json::from_str(contents)?;
+    if !raw.is_object() {
+        return Err("package.json must be a JSON object".into());
+    }
+    let package = PackageJson::deserialize(&raw)?;
+
+    Ok((package, raw, indent))
+}
+
+/// Whether `path` is a Deno manifest, whose `imports` replace package.json dependencies.
+pub fn is_deno_manifest(path: &Path) -> bool {
+    matches!(
+        path.file_name().and_then(|name| name.to_str()),
+        Some("deno.json" | "jsr.json")
+    )
+}
+
+/// Splits an `npm:` import specifier such as `npm:@scope/name@^1.0.0` into the package name and
+/// its range, other specifiers (`jsr:`, URLs) are not handled.
+pub fn parse_npm_specifier(specifier: &str) -> Option<(&str, &str)> {
+    let specifier = specifier.strip_prefix("npm:")?.trim_start_matches('/');
+    match specifier.get(1..)?.find('@') {
+        Some(separator) => Some((&specifier[..=separator], &specifier[separator + 2..])),
+        None => Some((specifier, "*")),
+    }
+}
+
+/// Parses a Deno manifest, exposing its `npm:` imports as dependencies.
+pub fn parse_deno_manifest(path: &PathBuf) -> Result<(PackageJson, Value, Indent), Box<dyn Error>> {
+    let contents = read_file(path)?;
 
     let indent = detect_indent(&contents);
-    let package = serde_json::from_str(&contents)?;
-    let raw = serde_json::from_str(&contents)?;
+    let raw: Value = serde_json::from_str(&contents)?;
+    let manifest = DenoJson::deserialize(&raw)?;
+    let dependencies: Dependencies = manifest
+        .imports
+        .values()
+        .filter_map(|specifier| parse_npm_specifier(specifier))
+        .map(|(name, range)| {
+            (
+                name.to_string(),
+                DependencySpecifier::Version(range.to_string()),
+            )
+        })
+        .collect();
+    let package = PackageJson {
+        name: manifest.name.unwrap_or_default(),
+        dependencies: Some(dependencies),
+        dev_dependencies: None,
+        optional_dependencies: None,
+        overrides: None,
+        resolutions: None,
+        bundle_dependencies: None,
+        package_manager: None,
+        engines: None,
+        workspaces: None,
+    };
 
     Ok((package, raw, indent))
 }
 
-fn parse_npm_lock(path: &PathBuf) -> Result<NpmLock, Box<dyn Error>> {
-    let mut contents = String::new();
-    File::open(path)?.read_to_string(&mut contents)?;
-
-    let json: JsonValue = serde_json::from_str(&contents)?;
-
-    match json.get("lockfileVersion") {
-        Some(lockfile_version) => {
-            let lockfile_version: u8 = serde_json::from_value(lockfile_version.clone())?;
-            match lockfile_version {
-                1 => Ok(NpmLock::Version1(serde_json::from_str(&contents)?)),
-                2 => Ok(NpmLock::Version2(serde_json::from_str(&contents)?)),
-                3 => Ok(NpmLock::Version3(serde_json::from_str(&contents)?)),
-                _ => Err("Unsupported lockfile version".into()),
+/// A lock file version this crate does not know how to read yet.
+#[derive(Debug)]
+pub struct UnsupportedLockVersion(pub String);
+
+impl fmt::Display for UnsupportedLockVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unsupported lockfile version {}", self.0)
+    }
+}
+
+impl Error for UnsupportedLockVersion {}
+
+/// Lock files larger than this many bytes are refused unless a higher limit is given.
+pub const DEFAULT_MAX_LOCK_FILE_SIZE: u64 = 100 * 1024 * 1024;
+
+/// A lock file larger than the size limit, refused before being read.
+#[derive(Debug)]
+pub struct LockFileTooLarge {
+    pub path: PathBuf,
+    pub size: u64,
+    pub max_size: u64,
+}
+
+impl fmt::Display for LockFileTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is {} bytes, over the limit of {} bytes",
+            self.path.display(),
+            self.size,
+            self.max_size
+        )
+    }
+}
+
+impl Error for LockFileTooLarge {}
+
+/// Reads a lock file, refusing it from its metadata when over `max_size` bytes so a huge file
+/// never gets loaded in memory. The standard input has no size to check beforehand.
+fn read_lock_file(path: &PathBuf, max_size: u64) -> Result<String, Box<dyn Error>> {
+    if path.as_os_str() != STDIN_PATH {
+        let size = path
+            .metadata()
+            .map_err(|err| anyhow!("failed to open {}: {err}", path.display()))?
+            .len();
+        if size > max_size {
+            return Err(LockFileTooLarge {
+                path: path.clone(),
+                size,
+                max_size,
             }
+            .into());
         }
-        None => Err("lockfileVersion field not found".into()),
     }
+
+    Ok(read_file(path)?)
 }
 
-fn parse_yarn_lock(path: &PathBuf) -> Result<YarnLockV2, Box<dyn Error>> {
-    let is_yarn_lock_v1 = Regex::new(r"# yarn lockfile v1")
-        .expect("Failed to create regex pattern for identifying yarn lockfile v1");
-    let is_yarn_lock_v2 = Regex::new(r"__metadata:\s*version: (\d)[\r\n]")
-        .expect("Failed to create regex pattern for identifying yarn lockfile v2");
+fn deserialize_npm_lock_content_by_version(
+    contents: &str,
+    version: &str,
+) -> Result<NpmLock, Box<dyn Error>> {
+    match version {
+        "1" => Ok(NpmLock::Version1(serde_json::from_str(contents)?)),
+        "2" => Ok(NpmLock::Version2(serde_json::from_str(contents)?)),
+        "3" => Ok(NpmLock::Version3(serde_json::from_str(contents)?)),
+        _ => Err(UnsupportedLockVersion(version.to_string()).into()),
+    }
+}
 
-    let mut contents = String::new();
-    File::open(path)?.read_to_string(&mut contents)?;
+fn parse_npm_lock(contents: &str) -> Result<NpmLock, Box<dyn Error>> {
+    let mut json: JsonValue = serde_json::from_str(contents)?;
+
+    if let Some(lockfile_version) = json.get("lockfileVersion") {
+        let lockfile_version: u8 = serde_json::from_value(lockfile_version.clone())?;
+        return deserialize_npm_lock_content_by_version(contents, &lockfile_version.to_string());
+    }
 
-    if is_yarn_lock_v1.is_match(&contents) {
-        Err("Yarn lock v1 parsing is not implemented yet.".into())
-    } else if is_yarn_lock_v2.is_match(&contents) {
-        Ok(serde_yml::from_str(&contents)?)
+    // Hand-edited lock files may omit the version, guess it from their layout.
+    let lockfile_version: u8 = if json.get("packages").is_some() {
+        3
+    } else if json.get("dependencies").is_some() {
+        1
     } else {
-        Err("Yarn lock file version parsing is not implemented yet.".into())
+        return Err("lockfileVersion field not found".into());
+    };
+    debug!("lockfileVersion field not found, reading the lock file as version {lockfile_version}.");
+    json["lockfileVersion"] = lockfile_version.into();
+    deserialize_npm_lock_content_by_version(
+        &serde_json::to_string(&json)?,
+        &lockfile_version.to_string(),
+    )
+}
+
+pub fn detect_yarn_lock_version(contents: &str) -> Option<YarnLockVersion> {
+    let is_yarn_lock_v1 = Regex::new(r"(?m)^#\s*yarn lockfile v1\s*$")
+        .expect("Failed to create regex pattern for identifying yarn lockfile v1");
+    let yarn_lock_berry_version = Regex::new(
+        r#"(?m)^__metadata:\s*$(?:\r?\n[ \t]+.*$)*?\r?\n[ \t]+version:\s*"?(\d+)"?\s*$"#,
+    )
+    .expect("Failed to create regex pattern for identifying yarn lockfile berry version");
+
+    if is_yarn_lock_v1.is_match(contents) {
+        return Some(YarnLockVersion::V1);
+    }
+
+    yarn_lock_berry_version
+        .captures(contents)
+        .and_then(|captures| captures[1].parse().ok())
+        .map(YarnLockVersion::Berry)
+}
+
+fn deserialize_yarn_lock_content_by_version(
+    contents: &str,
+    version: YarnLockVersion,
+) -> Result<YarnLockV2, Box<dyn Error>> {
+    match version {
+        YarnLockVersion::V1 => Err("Yarn lock v1 parsing is not implemented yet.".into()),
+        YarnLockVersion::Berry(version) => {
+            debug!("Yarn lock file uses metadata version {}.", version);
+            Ok(serde_yml::from_str(contents)?)
+        }
+    }
+}
+
+/// Whether a lock file holds nothing but comments and blank lines, as right after `yarn init`.
+fn is_effectively_empty(contents: &str) -> bool {
+    contents
+        .lines()
+        .map(str::trim)
+        .all(|line| line.is_empty() || line.starts_with('#'))
+}
+
+fn parse_yarn_lock(contents: &str) -> Result<YarnLockV2, Box<dyn Error>> {
+    if is_effectively_empty(contents) {
+        debug!("Yarn lock file is empty.");
+        return Ok(YarnLockV2::new());
+    }
+
+    match detect_yarn_lock_version(contents) {
+        Some(version) => deserialize_yarn_lock_content_by_version(contents, version),
+        None => Err(UnsupportedLockVersion("unknown".to_string()).into()),
     }
 }
 
@@ -66,26 +266,53 @@ fn deserialize_pnpm_lock_content_by_version(
     contents: &str,
     version: &str,
 ) -> Result<PnpmLock, Box<dyn Error>> {
-    match version {
+    // An unquoted `lockfileVersion: 6` is read as the integer 6.
+    let version = if version.contains('.') {
+        version.to_string()
+    } else {
+        format!("{version}.0")
+    };
+
+    match version.as_str() {
         "5.4" => Ok(PnpmLock::Version5(serde_yml::from_str(contents)?)),
         "6.0" => Ok(PnpmLock::Version6(serde_yml::from_str(contents)?)),
-        _ => Err("Unsupported lockfile version".into()),
+        _ => Err(UnsupportedLockVersion(version).into()),
     }
 }
 
-fn parse_pnpm_lock(path: &PathBuf) -> Result<PnpmLock, Box<dyn Error>> {
-    let mut contents = String::new();
-    File::open(path)?.read_to_string(&mut contents)?;
+/// Top level `lockfileVersion` of a pnpm lock file, read from its line near the top so large
+/// lock files are parsed once.
+fn scan_pnpm_lock_version(contents: &str) -> Option<&str> {
+    contents.lines().find_map(|line| {
+        let value = line.strip_prefix("lockfileVersion:")?;
+        let value = value.split(" #").next().unwrap_or_default().trim();
+        let value = value
+            .strip_prefix('\'')
+            .and_then(|value| value.strip_suffix('\''))
+            .or_else(|| {
+                value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+            })
+            .unwrap_or(value);
+        (!value.is_empty()).then_some(value)
+    })
+}
+
+fn parse_pnpm_lock(contents: &str) -> Result<PnpmLock, Box<dyn Error>> {
+    if let Some(version) = scan_pnpm_lock_version(contents) {
+        return deserialize_pnpm_lock_content_by_version(contents, version);
+    }
 
-    let yaml: YamlValue = serde_yml::from_str(&contents)?;
+    let yaml: YamlValue = serde_yml::from_str(contents)?;
 
     match yaml.get("lockfileVersion") {
         Some(lockfile_version) => match lockfile_version {
             YamlValue::Number(version_number) => {
-                deserialize_pnpm_lock_content_by_version(&contents, &version_number.to_string())
+                deserialize_pnpm_lock_content_by_version(contents, &version_number.to_string())
             }
             YamlValue::String(version_str) => {
-                deserialize_pnpm_lock_content_by_version(&contents, version_str)
+                deserialize_pnpm_lock_content_by_version(contents, version_str)
             }
             _ => Err("Invalid lockfileVersion type".into()),
         },
@@ -93,14 +320,640 @@ fn parse_pnpm_lock(path: &PathBuf) -> Result<PnpmLock, Box<dyn Error>> {
     }
 }
 
-pub fn parse_lock(lockfile_result: &LockFileResult) -> Result<PackageManagerLock, Box<dyn Error>> {
+/// Turns JSONC into JSON by dropping `//` and `/* */` comments and trailing commas, leaving
+/// strings untouched.
+fn strip_jsonc(contents: &str) -> String {
+    let mut json = String::with_capacity(contents.len());
+    let mut chars = contents.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(char) = chars.next() {
+        if in_string {
+            json.push(char);
+            match char {
+                '\\' => json.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match (char, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                json.push(char);
+            }
+            ('/', Some('/')) => while chars.next_if(|next| *next != '\n').is_some() {},
+            ('/', Some('*')) => {
+                chars.next();
+                while let Some(next) = chars.next() {
+                    if next == '*' && chars.next_if_eq(&'/').is_some() {
+                        break;
+                    }
+                }
+            }
+            ('}' | ']', _) => {
+                let trimmed_len = json.trim_end().len();
+                if json[..trimmed_len].ends_with(',') {
+                    json.remove(trimmed_len - 1);
+                }
+                json.push(char);
+            }
+            _ => json.push(char),
+        }
+    }
+
+    json
+}
+
+fn deserialize_bun_lock_content(contents: &str) -> Result<BunLock, Box<dyn Error>> {
+    let lock: BunLock = serde_json::from_str(&strip_jsonc(contents))?;
+    debug!("Bun lock file uses version {}.", lock.lockfile_version);
+    Ok(lock)
+}
+
+/// Parses lock file contents already in memory, sniffing the lock file version.
+pub fn parse_lock_content(
+    contents: &str,
+    package_manager: PackageManager,
+) -> Result<PackageManagerLock, Box<dyn Error>> {
+    match package_manager {
+        PackageManager::Npm => parse_npm_lock(contents).map(PackageManagerLock::Npm),
+        PackageManager::Yarn => parse_yarn_lock(contents).map(PackageManagerLock::Yarn),
+        PackageManager::Pnpm => parse_pnpm_lock(contents).map(PackageManagerLock::Pnpm),
+        PackageManager::Bun => deserialize_bun_lock_content(contents).map(PackageManagerLock::Bun),
+    }
+}
+
+/// Reads and parses a lock file, refusing it when over `max_size` bytes.
+pub fn parse_lock(
+    lockfile_result: &LockFileResult,
+    max_size: u64,
+) -> Result<PackageManagerLock, Box<dyn Error>> {
+    parse_lock_content(
+        &read_lock_file(&lockfile_result.path, max_size)?,
+        lockfile_result.package_manager,
+    )
+}
+
+/// Same as [`parse_lock`] but trusts `version` instead of sniffing it from the file: the
+/// npm `lockfileVersion` (`"3"`), the yarn lockfile or metadata version (`"1"`, `"8"`) or
+/// the pnpm `lockfileVersion` (`"6.0"`). Bun lock files are read the same whatever the version.
+pub fn parse_lock_with_version(
+    lockfile_result: &LockFileResult,
+    version: &str,
+    max_size: u64,
+) -> Result<PackageManagerLock, Box<dyn Error>> {
+    let contents = read_lock_file(&lockfile_result.path, max_size)?;
+
     match &lockfile_result.package_manager {
-        PackageManager::Npm => parse_npm_lock(&lockfile_result.path).map(PackageManagerLock::Npm),
+        PackageManager::Npm => {
+            deserialize_npm_lock_content_by_version(&contents, version).map(PackageManagerLock::Npm)
+        }
         PackageManager::Yarn => {
-            parse_yarn_lock(&lockfile_result.path).map(PackageManagerLock::Yarn)
+            let version = match version.parse()? {
+                1 => YarnLockVersion::V1,
+                version => YarnLockVersion::Berry(version),
+            };
+            deserialize_yarn_lock_content_by_version(&contents, version)
+                .map(PackageManagerLock::Yarn)
+        }
+        PackageManager::Pnpm => deserialize_pnpm_lock_content_by_version(&contents, version)
+            .map(PackageManagerLock::Pnpm),
+        PackageManager::Bun => deserialize_bun_lock_content(&contents).map(PackageManagerLock::Bun),
+    }
+}
+
+/// Newest lock file version of `package_manager` this crate reads, in the form
+/// [`parse_lock_with_version`] expects.
+fn newest_lock_version(package_manager: PackageManager) -> &'static str {
+    match package_manager {
+        PackageManager::Npm => "3",
+        PackageManager::Yarn => "8",
+        PackageManager::Pnpm => "6.0",
+        PackageManager::Bun => "1",
+    }
+}
+
+/// Same as [`parse_lock`], reading a lock file of an unsupported version as the newest supported
+/// one instead of failing. The result is best effort.
+pub fn parse_lock_allowing_unknown_version(
+    lockfile_result: &LockFileResult,
+    max_size: u64,
+) -> Result<PackageManagerLock, Box<dyn Error>> {
+    match parse_lock(lockfile_result, max_size) {
+        Err(err) if err.is::<UnsupportedLockVersion>() => {
+            let version = newest_lock_version(lockfile_result.package_manager);
+            warn!(
+                "[WARN] {}: {}, reading it as a version {} lock file.",
+                lockfile_result.path.display(),
+                err,
+                version
+            );
+            parse_lock_with_version(lockfile_result, version, max_size)
         }
-        PackageManager::Pnpm => {
-            parse_pnpm_lock(&lockfile_result.path).map(PackageManagerLock::Pnpm)
+        result => result,
+    }
+}
+
+pub fn parse_npmrc_content(contents: &str) -> Npmrc {
+    let mut npmrc = Npmrc::default();
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
         }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim().trim_matches('"'));
+
+        if key == "registry" {
+            npmrc.registry = Some(value.to_string());
+        } else if let Some(scope) = key.strip_suffix(":registry") {
+            npmrc
+                .scoped_registries
+                .insert(scope.to_string(), value.to_string());
+        } else if let Some(registry) = key.strip_suffix(":_authToken") {
+            npmrc
+                .auth_tokens
+                .insert(registry.to_string(), value.to_string());
+        } else if key == "engine-strict" {
+            npmrc.engine_strict = value == "true";
+        }
+    }
+
+    npmrc
+}
+
+pub fn parse_npmrc(path: &PathBuf) -> Result<Npmrc, Box<dyn Error>> {
+    let contents = read_file(path)?;
+
+    Ok(parse_npmrc_content(&contents))
+}
+
+pub fn parse_npdrc(path: &PathBuf) -> Result<NpdConfig, Box<dyn Error>> {
+    let contents = read_file(path)?;
+
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// Glob patterns of an ignore file, one per line, blank lines and `#` comments left out.
+pub fn parse_ignore_file(path: &PathBuf) -> Result<Vec<String>, Box<dyn Error>> {
+    let contents = read_file(path)?;
+
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(ToString::to_string)
+        .collect())
+}
+
+pub fn parse_yarnrc(path: &PathBuf) -> Result<Yarnrc, Box<dyn Error>> {
+    let contents = read_file(path)?;
+
+    Ok(serde_yml::from_str::<Option<Yarnrc>>(&contents)?.unwrap_or_default())
+}
+
+/// Node version of an asdf `.tool-versions` file, the first one listed on its `nodejs` line.
+fn parse_tool_versions_node(contents: &str) -> Option<&str> {
+    contents.lines().find_map(|line| {
+        let mut fields = line
+            .split('#')
+            .next()
+            .unwrap_or_default()
+            .split_whitespace();
+        matches!(fields.next(), Some("nodejs" | "node"))
+            .then(|| fields.next())
+            .flatten()
+    })
+}
+
+/// Node version or range of a `.nvmrc`, `.node-version` or `.tool-versions` file, `None` for
+/// aliases such as `lts/*` or `node` that cannot be compared.
+pub fn parse_node_version_file(path: &PathBuf) -> Result<Option<String>, Box<dyn Error>> {
+    let contents = read_file(path)?;
+    let version = if path.ends_with(TOOL_VERSIONS_FILE) {
+        parse_tool_versions_node(&contents)
+    } else {
+        contents
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default().trim())
+            .find(|line| !line.is_empty())
+    };
+
+    Ok(version
+        .map(|version| version.trim_start_matches('v'))
+        .filter(|version| version.starts_with(|c: char| c.is_ascii_digit()))
+        .map(ToString::to_string))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fixture_path;
+    use crate::types::YarnNodeLinker;
+
+    #[test]
+    fn detect_yarn_lock_versions() {
+        let read_fixture = |name: &str| {
+            read_file(&fixture_path(name).join("yarn.lock")).expect("Unable to read fixture")
+        };
+
+        assert_eq!(
+            detect_yarn_lock_version(&read_fixture("yarn-v1-crlf")),
+            Some(YarnLockVersion::V1)
+        );
+        assert_eq!(
+            detect_yarn_lock_version(&read_fixture("yarn-berry-crlf")),
+            Some(YarnLockVersion::Berry(8))
+        );
+        assert_eq!(
+            detect_yarn_lock_version("__metadata:\n  cacheKey: 10c0\n  version: 12\n"),
+            Some(YarnLockVersion::Berry(12))
+        );
+        assert_eq!(detect_yarn_lock_version("# just a comment\n"), None);
+    }
+
+    #[test]
+    fn open_errors_name_the_file() {
+        let path = fixture_path("missing").join("package.json");
+        let err = parse_package(&path).expect_err("Expected a missing package.json");
+
+        let io_error = File::open(&path).expect_err("Expected a missing file");
+        assert_eq!(
+            err.to_string(),
+            format!("failed to open {}: {io_error}", path.display())
+        );
+
+        let err = parse_package(&fixture_path("npm-sections")).expect_err("Expected a directory");
+        assert!(err.to_string().contains(&format!(
+            "failed to open {}: ",
+            fixture_path("npm-sections").display()
+        )));
+    }
+
+    #[test]
+    fn npm_lock_version_is_guessed_from_its_layout() {
+        let lock = parse_lock(
+            &LockFileResult {
+                path: fixture_path("npm-no-lockfile-version").join("package-lock.json"),
+                package_manager: PackageManager::Npm,
+            },
+            DEFAULT_MAX_LOCK_FILE_SIZE,
+        )
+        .expect("Unable to parse lock file without lockfileVersion");
+
+        assert!(matches!(
+            lock,
+            PackageManagerLock::Npm(NpmLock::Version3(lock)) if lock.packages.contains_key("node_modules/lodash")
+        ));
+    }
+
+    #[test]
+    fn pnpm_integer_lock_version() {
+        let lock = parse_lock(
+            &LockFileResult {
+                path: fixture_path("pnpm-v6-integer-version").join("pnpm-lock.yaml"),
+                package_manager: PackageManager::Pnpm,
+            },
+            DEFAULT_MAX_LOCK_FILE_SIZE,
+        )
+        .expect("Unable to parse pnpm lock file with an integer version");
+
+        assert!(matches!(
+            lock,
+            PackageManagerLock::Pnpm(PnpmLock::Version6(_))
+        ));
+    }
+
+    #[test]
+    fn empty_yarn_lock_has_no_dependencies() {
+        let lock = parse_lock(
+            &LockFileResult {
+                path: fixture_path("yarn-empty-lock").join("yarn.lock"),
+                package_manager: PackageManager::Yarn,
+            },
+            DEFAULT_MAX_LOCK_FILE_SIZE,
+        )
+        .expect("Unable to parse empty yarn lock file");
+
+        assert!(matches!(lock, PackageManagerLock::Yarn(lock) if lock.is_empty()));
+    }
+
+    #[test]
+    fn package_json_must_be_an_object() {
+        let err = parse_package(&fixture_path("npm-array-package").join("package.json"))
+            .expect_err("Expected a top-level array to be rejected");
+
+        assert_eq!(err.to_string(), "package.json must be a JSON object");
+    }
+
+    #[test]
+    fn parsed_package_matches_raw_package() {
+        let (package, raw, _) = parse_package(&fixture_path("npm-sections").join("package.json"))
+            .expect("Unable to parse package.json fixture");
+
+        assert_eq!(raw["name"], package.name.as_str());
+        assert_eq!(
+            serde_json::to_value(&package.dev_dependencies).expect("Unable to serialize"),
+            raw["devDependencies"]
+        );
+        assert_eq!(
+            serde_json::to_value(&package.optional_dependencies).expect("Unable to serialize"),
+            raw["optionalDependencies"]
+        );
+    }
+
+    #[test]
+    fn parse_lock_with_known_version() {
+        let lock = |name: &str, file_name: &str, package_manager| LockFileResult {
+            path: fixture_path(name).join(file_name),
+            package_manager,
+        };
+        let npm_lock = lock("npm-sections", "package-lock.json", PackageManager::Npm);
+        let pnpm_lock = lock(
+            "pnpm-v6-peer-suffix",
+            "pnpm-lock.yaml",
+            PackageManager::Pnpm,
+        );
+        let yarn_lock = lock("yarn-berry-crlf", "yarn.lock", PackageManager::Yarn);
+
+        assert!(matches!(
+            parse_lock_with_version(&npm_lock, "3", DEFAULT_MAX_LOCK_FILE_SIZE),
+            Ok(PackageManagerLock::Npm(NpmLock::Version3(_)))
+        ));
+        assert!(matches!(
+            parse_lock(&npm_lock, DEFAULT_MAX_LOCK_FILE_SIZE),
+            Ok(PackageManagerLock::Npm(NpmLock::Version3(_)))
+        ));
+        assert!(matches!(
+            parse_lock_with_version(&pnpm_lock, "6.0", DEFAULT_MAX_LOCK_FILE_SIZE),
+            Ok(PackageManagerLock::Pnpm(PnpmLock::Version6(_)))
+        ));
+        assert!(matches!(
+            parse_lock(&pnpm_lock, DEFAULT_MAX_LOCK_FILE_SIZE),
+            Ok(PackageManagerLock::Pnpm(PnpmLock::Version6(_)))
+        ));
+        assert!(matches!(
+            parse_lock_with_version(&yarn_lock, "8", DEFAULT_MAX_LOCK_FILE_SIZE),
+            Ok(PackageManagerLock::Yarn(_))
+        ));
+
+        assert!(parse_lock_with_version(&npm_lock, "9", DEFAULT_MAX_LOCK_FILE_SIZE).is_err());
+        assert!(parse_lock_with_version(&pnpm_lock, "9.0", DEFAULT_MAX_LOCK_FILE_SIZE).is_err());
+        assert!(parse_lock_with_version(&yarn_lock, "1", DEFAULT_MAX_LOCK_FILE_SIZE).is_err());
+    }
+
+    #[test]
+    fn parse_deno_manifest_npm_imports() {
+        let path = fixture_path("deno-npm-imports").join("deno.json");
+        let (package, _, _) = parse_deno_manifest(&path).expect("Unable to parse deno.json");
+        let mut dependencies: Vec<(String, String)> = package
+            .dependencies
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(name, specifier)| Some((name, specifier.as_str()?.to_string())))
+            .collect();
+        dependencies.sort();
+
+        assert!(is_deno_manifest(&path));
+        assert_eq!(package.name, "@scope/deno-npm-imports");
+        assert_eq!(
+            dependencies,
+            vec![
+                ("@types/lodash".to_string(), "^4.14.0".to_string()),
+                ("chalk".to_string(), "*".to_string()),
+                ("lodash".to_string(), "^4.17.0".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_npm_specifier("npm:/@types/lodash@^4.14.0"),
+            Some(("@types/lodash", "^4.14.0"))
+        );
+        assert_eq!(parse_npm_specifier("jsr:@std/path@^1.0.0"), None);
+    }
+
+    #[test]
+    fn parse_node_version_files() {
+        let parse = |name: &str, file_name: &str| {
+            parse_node_version_file(&fixture_path(name).join(file_name))
+                .expect("Unable to parse node version file")
+        };
+
+        assert_eq!(parse("nvmrc", ".nvmrc"), Some("16.20.2".to_string()));
+        assert_eq!(
+            parse("node-version", ".node-version"),
+            Some("18".to_string())
+        );
+        assert_eq!(
+            parse("tool-versions", ".tool-versions"),
+            Some("18.19.0".to_string())
+        );
+
+        assert_eq!(
+            parse_tool_versions_node("ruby 3.3.0\nnodejs 20.11.0 18.19.0 # LTS\n"),
+            Some("20.11.0")
+        );
+        assert_eq!(parse_tool_versions_node("nodejs\n"), None);
+        assert_eq!(parse_tool_versions_node("python 3.12.1\n"), None);
+    }
+
+    #[test]
+    fn scan_pnpm_lock_versions() {
+        assert_eq!(
+            scan_pnpm_lock_version("lockfileVersion: '6.0'\n\nimporters: {}\n"),
+            Some("6.0")
+        );
+        assert_eq!(
+            scan_pnpm_lock_version("lockfileVersion: \"6.0\"\r\n"),
+            Some("6.0")
+        );
+        assert_eq!(
+            scan_pnpm_lock_version("lockfileVersion: 5.4 # pnpm 7\n"),
+            Some("5.4")
+        );
+        assert_eq!(
+            scan_pnpm_lock_version("packages:\n  /a@1.0.0:\n    lockfileVersion: 1\n"),
+            None
+        );
+        assert_eq!(scan_pnpm_lock_version("{ lockfileVersion: '6.0' }"), None);
+
+        for name in [
+            "pnpm-v5-specifiers",
+            "pnpm-v6-integer-version",
+            "pnpm-v6-peer-suffix",
+        ] {
+            let contents = read_file(&fixture_path(name).join("pnpm-lock.yaml"))
+                .expect("Unable to read fixture");
+            assert!(scan_pnpm_lock_version(&contents).is_some(), "{name}");
+        }
+    }
+
+    #[test]
+    fn parse_npmrc_engine_strict() {
+        let npmrc = parse_npmrc(&fixture_path("npm-engine-strict").join(".npmrc"))
+            .expect("Unable to parse .npmrc fixture");
+        assert!(npmrc.engine_strict);
+
+        assert!(!parse_npmrc_content("engine-strict=false").engine_strict);
+        assert!(!parse_npmrc_content("registry=https://registry.example.com/").engine_strict);
+    }
+
+    #[test]
+    fn parse_lock_refuses_lock_files_over_the_size_limit() {
+        let lock = LockFileResult {
+            path: fixture_path("npm-sections").join("package-lock.json"),
+            package_manager: PackageManager::Npm,
+        };
+        let size = lock.path.metadata().expect("Missing fixture").len();
+
+        assert!(parse_lock(&lock, size).is_ok());
+        let Err(err) = parse_lock(&lock, size - 1) else {
+            panic!("Expected the lock file to be refused");
+        };
+        let err = err
+            .downcast::<LockFileTooLarge>()
+            .expect("Expected a size limit error");
+        assert_eq!((err.size, err.max_size), (size, size - 1));
+    }
+
+    #[test]
+    fn parse_unknown_lock_version_as_the_newest() {
+        let lock = LockFileResult {
+            path: fixture_path("npm-unknown-lockfile-version").join("package-lock.json"),
+            package_manager: PackageManager::Npm,
+        };
+
+        assert!(parse_lock(&lock, DEFAULT_MAX_LOCK_FILE_SIZE)
+            .is_err_and(|err| err.is::<UnsupportedLockVersion>()));
+        assert!(matches!(
+            parse_lock_allowing_unknown_version(&lock, DEFAULT_MAX_LOCK_FILE_SIZE),
+            Ok(PackageManagerLock::Npm(NpmLock::Version3(_)))
+        ));
+    }
+
+    #[test]
+    fn strip_jsonc_comments_and_trailing_commas() {
+        let jsonc = r#"{
+  // comment
+  "url": "https://example.com/*not-a-comment*/",
+  "list": [1, 2, /* inline */ 3,],
+  "quoted": "a \"//\" b",
+}"#;
+
+        assert_eq!(
+            serde_json::from_str::<Value>(&strip_jsonc(jsonc)).expect("Invalid JSON"),
+            serde_json::json!({
+                "url": "https://example.com/*not-a-comment*/",
+                "list": [1, 2, 3],
+                "quoted": "a \"//\" b"
+            })
+        );
+    }
+
+    #[test]
+    fn parse_yarnrc_node_linker() {
+        let yarnrc = parse_yarnrc(&fixture_path("yarn-berry-ranges").join(".yarnrc.yml"))
+            .expect("Unable to parse .yarnrc.yml fixture");
+        assert_eq!(yarnrc.node_linker(), YarnNodeLinker::NodeModules);
+        assert_eq!(yarnrc.nm_mode.as_deref(), Some("hardlinks-local"));
+
+        assert_eq!(Yarnrc::default().node_linker(), YarnNodeLinker::Pnp);
+    }
+
+    /// Average duration of `runs` calls of `f`, for the ignored benchmarks below.
+    fn average_duration<T>(runs: u32, mut f: impl FnMut() -> T) -> std::time::Duration {
+        std::hint::black_box(f());
+        let started_at = std::time::Instant::now();
+        for _ in 0..runs {
+            std::hint::black_box(f());
+        }
+        started_at.elapsed() / runs
+    }
+
+    /// pnpm v6 lock file of `count` packages, each one a dependency of the root importer.
+    fn large_pnpm_lock(count: usize) -> String {
+        use std::fmt::Write;
+
+        let mut importer = String::new();
+        let mut packages = String::new();
+        for index in 0..count {
+            write!(
+                importer,
+                "      package-{index}:\n        specifier: ^1.{index}.0\n        version: 1.{index}.3\n"
+            )
+            .expect("Unable to write importer entry");
+            write!(
+                packages,
+                "  /package-{index}@1.{index}.3:\n    resolution: {{integrity: sha512-{index}}}\n    engines: {{node: '>=14'}}\n    dev: false\n\n"
+            )
+            .expect("Unable to write package entry");
+        }
+
+        format!(
+            "lockfileVersion: '6.0'\n\nimporters:\n\n  .:\n    dependencies:\n{importer}\npackages:\n\n{packages}"
+        )
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with `cargo test --release -- --ignored --nocapture bench_`"]
+    fn bench_pnpm_lock_version_scan() {
+        let contents = large_pnpm_lock(20_000);
+
+        let scanned = average_duration(5, || parse_pnpm_lock(&contents).expect("Invalid lock"));
+        let parsed_twice = average_duration(5, || {
+            let yaml: YamlValue = serde_yml::from_str(&contents).expect("Invalid YAML");
+            let version = yaml["lockfileVersion"].as_str().expect("Missing version");
+            deserialize_pnpm_lock_content_by_version(&contents, version).expect("Invalid lock")
+        });
+
+        eprintln!(
+            "pnpm lock of {} bytes: {scanned:?} scanning its version, {parsed_twice:?} parsing it twice",
+            contents.len()
+        );
+        assert!(scanned < parsed_twice);
+    }
+
+    /// package.json declaring `count` dependencies and as many dev dependencies.
+    fn large_package_json(count: usize) -> String {
+        let dependencies = |prefix: &str| {
+            (0..count)
+                .map(|index| format!("\"{prefix}-{index}\": \"^1.{index}.0\""))
+                .collect::<Vec<_>>()
+                .join(",\n    ")
+        };
+
+        format!(
+            "{{\n  \"name\": \"large\",\n  \"dependencies\": {{\n    {}\n  }},\n  \"devDependencies\": {{\n    {}\n  }}\n}}\n",
+            dependencies("package"),
+            dependencies("dev-package")
+        )
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with `cargo test --release -- --ignored --nocapture bench_`"]
+    fn bench_package_json_single_parse() {
+        let contents = large_package_json(50_000);
+
+        let parsed_once = average_duration(10, || {
+            parse_package_content(&contents).expect("Invalid package")
+        });
+        let parsed_twice = average_duration(10, || {
+            let package: PackageJson = serde_json::from_str(&contents).expect("Invalid package");
+            let raw: Value = serde_json::from_str(&contents).expect("Invalid JSON");
+            (package, raw, detect_indent(&contents))
+        });
+
+        eprintln!(
+            "package.json of {} bytes: {parsed_once:?} parsing it once, {parsed_twice:?} parsing it twice",
+            contents.len()
+        );
+        // Building the typed view dominates and costs about as much from the `Value` as from the
+        // text, the two measure within a few percent: only guard against a real regression.
+        assert!(parsed_once <= parsed_twice * 5 / 4);
     }
 }
@@ -1,313 +1,380 @@
-mod finder;
-mod parser;
-mod types;
-
-use anyhow::Result;
-use clap::Parser;
-use clap_verbosity_flag::Verbosity;
+use anyhow::{anyhow, Context, Result};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use clap_verbosity_flag::{Verbosity, WarnLevel};
 use comfy_table::{presets, Table};
-use console::style;
+use console::{style, Color, Term};
 use detect_indent::Indent;
-use semver::Version;
-use serde::ser::Serialize;
-use serde_json::ser::PrettyFormatter;
+use riri_node_tools::dedupe::{self, LockedVersions};
+use riri_node_tools::engines::{self, EngineCheck};
+use riri_node_tools::finder::FindUpOptions;
+use riri_node_tools::pin::{
+    apply_pins, collapse_duplicate_sections, compute_pin_plan, explain_dependency,
+    serialize_package_json, unresolved_dependencies, DependencyExplanation, PinOptions, PinPlan,
+    PinStats, PinStyle, VersionToPin,
+};
+use riri_node_tools::resolver::{DependencyVersionResolver, ResolverCache};
+use riri_node_tools::types::{
+    DependencySection, NpdConfig, PackageJson, PackageManager, Workspaces,
+};
+use riri_node_tools::workspace::{self, PackagePins};
+use riri_node_tools::{finder, git, parser};
+use serde::Serialize;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fs::OpenOptions;
-use std::io::{Error, Write};
+use std::io::Write;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::process::ExitCode;
 use std::string::ToString;
-use tracing::{debug, error, info};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tracing::level_filters::LevelFilter;
+use tracing::{debug, error, info, warn};
 use tracing_log::AsTrace;
-use types::{
-    Engine, LockDependency, NpmDependencies, NpmLock, NpmLockEngines, ObjectEngines, PackageJson,
-    PackageManagerLock, PnpmLock, VersionedDependencyOrResolved, YarnLockV2,
-};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::reload;
+use tracing_subscriber::util::SubscriberInitExt;
 
-type ResolveDependencyKey = fn(name: &str, version: &str) -> String;
-type LockDependencies = HashMap<String, LockDependency>;
+/// Exit code for runtime errors, matching the one clap uses for usage errors.
+const EXIT_ERROR: u8 = 2;
+/// Exit code for failed checks, such as engines not satisfied under `engine-strict`.
+const EXIT_CHECK_FAILED: u8 = 1;
 
+/// Engine violations found while `.npmrc` sets `engine-strict`, which npm fails installs on.
 #[derive(Debug)]
-pub struct DependencyVersionResolver {
-    pub locked_dependencies: LockDependencies,
-    pub resolve_dependency_key: ResolveDependencyKey,
-}
-
-fn convert_array_to_object_engines(engines: Vec<String>) -> ObjectEngines {
-    let mut object_engines = ObjectEngines::new();
-
-    for engine_str in engines {
-        let lowercase_engine_str = engine_str.to_lowercase();
-
-        for engine_enum in [Engine::Node, Engine::Npm, Engine::Yarn] {
-            let engine_str_lowercase = format!("{engine_enum:?}").to_lowercase();
-            if lowercase_engine_str.contains(&engine_str_lowercase) {
-                let value = engine_str.trim_start_matches(&engine_str_lowercase).trim();
+struct EnginesNotSatisfied(usize);
 
-                object_engines.insert(engine_enum, value.to_owned());
-                break;
-            }
-        }
+impl std::fmt::Display for EnginesNotSatisfied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Found {} dependency engine violation{} while .npmrc sets engine-strict",
+            self.0,
+            if self.0 == 1 { "" } else { "s" }
+        )
     }
-
-    object_engines
 }
 
-fn convert_npm_engines_to_object_engines(engines: Option<NpmLockEngines>) -> Option<ObjectEngines> {
-    match engines {
-        Some(NpmLockEngines::Object(object_engines)) => Some(object_engines),
-        Some(NpmLockEngines::Array(array_engines)) => {
-            Some(convert_array_to_object_engines(array_engines))
-        }
-        _ => None,
+impl std::error::Error for EnginesNotSatisfied {}
+
+/// Versions left to pin when running `check`.
+#[derive(Debug)]
+struct VersionsToPin(usize);
+
+impl std::fmt::Display for VersionsToPin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Found {} dependency version{} to pin",
+            self.0,
+            if self.0 == 1 { "" } else { "s" }
+        )
     }
 }
 
-#[tracing::instrument]
-fn convert_npm_to_lock_dependencies(npm_dependencies: NpmDependencies) -> LockDependencies {
-    let mut lock_dependencies = LockDependencies::new();
+impl std::error::Error for VersionsToPin {}
 
-    for (dependency_name, versioned_or_resolved) in npm_dependencies.clone() {
-        if dependency_name.starts_with("node_modules/") {
-            continue;
-        }
-
-        let lock_dependency = match versioned_or_resolved {
-            VersionedDependencyOrResolved::Versioned(versioned_dependency) => LockDependency {
-                version: versioned_dependency.version,
-                engines: convert_npm_engines_to_object_engines(versioned_dependency.engines),
-            },
-            VersionedDependencyOrResolved::Resolved(resolved_dependency) => {
-                if let Some(resolved_key) = &resolved_dependency.resolved {
-                    debug!(
-                        "Dependency {} resolved using {}.",
-                        dependency_name, resolved_key
-                    );
-                    if let Some(resolved_dep) = npm_dependencies.get(resolved_key) {
-                        if let VersionedDependencyOrResolved::Versioned(versioned_dep) =
-                            resolved_dep
-                        {
-                            LockDependency {
-                                version: versioned_dep.version.clone(),
-                                engines: convert_npm_engines_to_object_engines(
-                                    versioned_dep.engines.clone(),
-                                ),
-                            }
-                        } else {
-                            debug!("Dependency {} version is undefined.", resolved_key);
-                            continue;
-                        }
-                    } else {
-                        debug!("Dependency {} is unresolved in dependencies.", resolved_key);
-                        continue;
-                    }
-                } else {
-                    continue;
-                }
-            }
-        };
+#[derive(Debug, Parser, Serialize)]
+#[command(author, version, about, long_about = None)]
+#[serde(rename_all = "camelCase")]
+#[allow(clippy::struct_excessive_bools)]
+struct Args {
+    #[command(subcommand)]
+    #[serde(skip)]
+    command: Option<Command>,
+    #[command(flatten)]
+    #[serde(skip)]
+    verbose: Verbosity<WarnLevel>,
+    #[arg(
+        short,
+        long,
+        default_value_t = false,
+        overrides_with = "no_update",
+        global = true
+    )]
+    update: bool,
+    /// Do not update package.json, even if `.npdrc` sets `update`
+    #[arg(
+        long,
+        default_value_t = false,
+        overrides_with = "update",
+        global = true
+    )]
+    #[serde(skip)]
+    no_update: bool,
+    #[arg(
+        short,
+        long,
+        value_name = "PATH",
+        conflicts_with = "workspaces",
+        global = true
+    )]
+    output: Option<PathBuf>,
+    #[arg(long, default_value_t = false, global = true)]
+    respect_overrides: bool,
+    #[arg(long, default_value_t = false, global = true)]
+    apply_resolutions: bool,
+    #[arg(long, default_value_t = false, global = true)]
+    prod: bool,
+    #[arg(long, default_value_t = false, global = true)]
+    ignore_optional: bool,
+    #[arg(long, default_value_t = false, global = true)]
+    allow_downgrade: bool,
+    #[arg(long, value_name = "GLOB", num_args = 1.., global = true)]
+    dev_scope: Vec<String>,
+    #[arg(long, value_name = "GLOB", num_args = 1.., global = true)]
+    ignore: Vec<String>,
+    #[arg(long, value_name = "PATH", global = true)]
+    ignore_file: Option<PathBuf>,
+    /// Patterns read from `--ignore-file`, merged with `--ignore`.
+    #[arg(skip)]
+    #[serde(skip)]
+    ignore_file_patterns: Vec<String>,
+    #[arg(long, value_name = "REF", conflicts_with = "workspaces", global = true)]
+    since: Option<String>,
+    #[arg(long, default_value_t = false, global = true)]
+    strip_prefix: bool,
+    #[arg(long, value_name = "STYLE", default_value_t = PinStyle::Exact, global = true)]
+    pin_style: PinStyle,
+    #[arg(long, default_value_t = false, global = true)]
+    dedupe_write: bool,
+    #[arg(long, default_value_t = false, global = true)]
+    summary_only: bool,
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with = "summary_only",
+        global = true
+    )]
+    group_by_scope: bool,
+    #[arg(long, default_value_t = false, global = true)]
+    timings: bool,
+    #[arg(long, value_name = "PATH", global = true)]
+    report_file: Option<PathBuf>,
+    #[arg(long, default_value_t = false, global = true)]
+    dedupe_report: bool,
+    #[arg(long, default_value_t = false, conflicts_with_all = ["update", "workspaces"], global = true)]
+    print_resolver: bool,
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["update", "workspaces"], global = true)]
+    explain: Option<String>,
+    #[arg(long, default_value_t = false, conflicts_with_all = ["update", "workspaces"], global = true)]
+    dump_unresolved: bool,
+    #[arg(long, default_value_t = false, conflicts_with_all = ["update", "workspaces"], global = true)]
+    check_engines: bool,
+    #[arg(long, default_value_t = false, conflicts_with_all = ["update", "workspaces"], global = true)]
+    #[serde(skip)]
+    config_print: bool,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table, global = true)]
+    format: OutputFormat,
+    #[arg(long, value_name = "DIR", global = true)]
+    cwd: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with = "workspaces",
+        global = true
+    )]
+    manifest: Option<PathBuf>,
+    #[arg(long, value_name = "PATH", env = "PACKAGE_LOCK", global = true)]
+    lockfile: Option<PathBuf>,
+    #[arg(long, value_name = "NAME", requires = "lockfile", global = true)]
+    package_manager: Option<PackageManager>,
+    #[arg(long, default_value_t = false, global = true)]
+    allow_unknown_lockfile_version: bool,
+    #[arg(long, value_name = "BYTES", default_value_t = parser::DEFAULT_MAX_LOCK_FILE_SIZE, global = true)]
+    max_lockfile_size: u64,
+    #[arg(long, value_name = "N", global = true)]
+    max_depth: Option<usize>,
+    #[arg(long, default_value_t = false, global = true)]
+    stop_at_git_root: bool,
+    #[arg(long, default_value_t = false, global = true)]
+    workspaces: bool,
+    #[arg(long, value_name = "N", requires = "workspaces", global = true)]
+    threads: Option<NonZeroUsize>,
+    #[arg(long, default_value_t = false, requires = "workspaces", global = true)]
+    member_locks: bool,
+}
 
-        lock_dependencies.insert(dependency_name, lock_dependency);
-    }
+/// Shorthands for the flags of the top-level command, which keeps working without any. Every
+/// flag is accepted after a subcommand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::Subcommand)]
+enum Command {
+    /// List the versions to pin, and write them to package.json with --update
+    Pin,
+    /// List the versions to pin, exiting with code 1 when there are any
+    Check,
+    /// Check the engines of the locked dependencies, same as --check-engines
+    Engines,
+}
 
-    lock_dependencies
+/// What gets printed for a given verbosity: `-qq` prints nothing, the default and
+/// `-q` only print results and errors, `-v` and above also report progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputMode {
+    Silent,
+    Results,
+    Progress,
 }
 
-#[tracing::instrument]
-fn npm_resolver(npm_lock: NpmLock) -> DependencyVersionResolver {
-    let resolve_dependency: ResolveDependencyKey = |name, _| name.to_string();
-    let resolve_package: ResolveDependencyKey = |name, _| format!("node_modules/{name}");
+/// How results are printed: aligned tables for humans, a JSON array or one JSON object per line
+/// for tooling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum OutputFormat {
+    Table,
+    Json,
+    Ndjson,
+}
 
-    match npm_lock {
-        NpmLock::Version1(lock) => DependencyVersionResolver {
-            locked_dependencies: convert_npm_to_lock_dependencies(lock.dependencies),
-            resolve_dependency_key: resolve_dependency,
-        },
-        NpmLock::Version2(lock) => {
-            if let Some(packages) = lock.packages {
-                DependencyVersionResolver {
-                    locked_dependencies: convert_npm_to_lock_dependencies(packages),
-                    resolve_dependency_key: resolve_package,
-                }
-            } else {
-                DependencyVersionResolver {
-                    locked_dependencies: convert_npm_to_lock_dependencies(lock.dependencies),
-                    resolve_dependency_key: resolve_dependency,
-                }
-            }
+impl From<&Verbosity<WarnLevel>> for OutputMode {
+    fn from(verbosity: &Verbosity<WarnLevel>) -> Self {
+        match verbosity.log_level() {
+            None => OutputMode::Silent,
+            Some(log::Level::Error | log::Level::Warn) => OutputMode::Results,
+            Some(_) => OutputMode::Progress,
         }
-        NpmLock::Version3(lock) => DependencyVersionResolver {
-            locked_dependencies: convert_npm_to_lock_dependencies(lock.packages),
-            resolve_dependency_key: resolve_package,
-        },
     }
 }
 
-fn transform_yarn_v2_to_lock_dependencies(yarn_lock: YarnLockV2) -> LockDependencies {
-    yarn_lock
-        .into_iter()
-        .map(|(name, dependency)| {
-            (
-                name,
-                LockDependency {
-                    version: dependency.version,
-                    engines: None,
-                },
-            )
-        })
-        .collect()
-}
-
-fn yarn_resolver(yarn_lock_file: YarnLockV2) -> DependencyVersionResolver {
-    DependencyVersionResolver {
-        locked_dependencies: transform_yarn_v2_to_lock_dependencies(yarn_lock_file),
-        resolve_dependency_key: |name, version| format!("{name}@npm:{version}"),
+impl From<&Args> for PinOptions {
+    fn from(args: &Args) -> Self {
+        PinOptions {
+            respect_overrides: args.respect_overrides,
+            apply_resolutions: args.apply_resolutions,
+            prod: args.prod,
+            ignore_optional: args.ignore_optional,
+            allow_downgrade: args.allow_downgrade,
+            dev_scopes: args.dev_scope.clone(),
+            ignore: args
+                .ignore
+                .iter()
+                .chain(&args.ignore_file_patterns)
+                .cloned()
+                .collect(),
+            strip_prefix: args.strip_prefix,
+            pin_style: args.pin_style,
+            only_dependencies: None,
+        }
     }
 }
 
-fn transform_pnpm_v5_to_lock_dependencies(
-    dependencies: Option<HashMap<String, String>>,
-) -> LockDependencies {
-    dependencies.map_or_else(HashMap::new, |deps| {
-        deps.into_iter()
-            .map(|(key, version)| {
-                (
-                    key,
-                    LockDependency {
-                        version,
-                        engines: None,
-                    },
-                )
-            })
-            .collect()
-    })
+impl From<&Args> for FindUpOptions {
+    fn from(args: &Args) -> Self {
+        FindUpOptions {
+            max_depth: args.max_depth,
+            stop_at_git_root: args.stop_at_git_root,
+        }
+    }
 }
 
-fn transform_pnpm_v6_to_lock_dependencies(
-    dependencies: Option<HashMap<String, LockDependency>>,
-) -> LockDependencies {
-    dependencies.map_or_else(HashMap::new, |deps| deps.into_iter().collect())
-}
+impl Args {
+    /// Takes the options of `config` the command line left unset, as told by `matches`.
+    fn apply_config(&mut self, config: &NpdConfig, matches: &ArgMatches) -> Result<()> {
+        let from_command_line = |id: &str| {
+            !matches!(
+                matches.value_source(id),
+                None | Some(ValueSource::DefaultValue)
+            )
+        };
+        macro_rules! apply {
+            (Some: $($field:ident),+) => {$(
+                if let Some(value) = &config.$field {
+                    if !from_command_line(stringify!($field)) {
+                        self.$field = Some(value.clone());
+                    }
+                }
+            )+};
+            ($($field:ident),+) => {$(
+                if let Some(value) = &config.$field {
+                    if !from_command_line(stringify!($field)) {
+                        self.$field = value.clone();
+                    }
+                }
+            )+};
+        }
 
-fn pnpm_resolver(pnpm_lock: PnpmLock) -> DependencyVersionResolver {
-    let locked_dependencies: LockDependencies = match pnpm_lock {
-        PnpmLock::Version6(lock) => {
-            let importer = lock
-                .importers
-                .get(".")
-                .cloned()
-                .expect("Expect Pnpm to have resolved dependencies in current directory.");
-            let dependencies = transform_pnpm_v6_to_lock_dependencies(importer.dependencies);
-            let dev_dependencies =
-                transform_pnpm_v6_to_lock_dependencies(importer.dev_dependencies);
-            let optional_dependencies =
-                transform_pnpm_v6_to_lock_dependencies(importer.optional_dependencies);
-
-            [dependencies, dev_dependencies, optional_dependencies]
-                .into_iter()
-                .flatten()
-                .collect()
+        if !from_command_line("no_update") {
+            apply!(update);
         }
-        PnpmLock::Version5(lock) => {
-            let importer = lock
-                .importers
-                .get(".")
-                .cloned()
-                .expect("Expect Pnpm to have resolved dependencies in current directory.");
-            let dependencies = transform_pnpm_v5_to_lock_dependencies(importer.dependencies);
-            let dev_dependencies =
-                transform_pnpm_v5_to_lock_dependencies(importer.dev_dependencies);
-            let optional_dependencies =
-                transform_pnpm_v5_to_lock_dependencies(importer.optional_dependencies);
-
-            [dependencies, dev_dependencies, optional_dependencies]
-                .into_iter()
-                .flatten()
-                .collect()
+        apply!(
+            respect_overrides,
+            apply_resolutions,
+            prod,
+            ignore_optional,
+            allow_downgrade,
+            dev_scope,
+            ignore,
+            strip_prefix,
+            pin_style,
+            dedupe_write,
+            summary_only,
+            group_by_scope,
+            timings,
+            dedupe_report,
+            allow_unknown_lockfile_version,
+            max_lockfile_size,
+            stop_at_git_root,
+            workspaces,
+            member_locks
+        );
+        apply!(Some: ignore_file, report_file, max_depth, threads);
+        if let Some(format) = &config.format {
+            if !from_command_line("format") {
+                self.format = OutputFormat::from_str(format, false)
+                    .map_err(|err| anyhow!("Invalid format in .npdrc: {err}"))?;
+            }
         }
-    };
 
-    DependencyVersionResolver {
-        locked_dependencies,
-        resolve_dependency_key: |name, _| name.to_string(),
+        Ok(())
     }
 }
 
-#[derive(Debug, Clone)]
-struct VersionToPin {
-    dependency: String,
-    package_version: String,
-    locked_version: String,
-}
+/// Duration of every step run so far, in the order they finished.
+#[derive(Debug, Default)]
+struct Timings(Mutex<Vec<(String, Duration)>>);
 
-#[tracing::instrument(skip_all)]
-fn compute_versions_to_pin(
-    package_json: &PackageJson,
-    resolver: &DependencyVersionResolver,
-) -> Result<Vec<VersionToPin>, Error> {
-    let mut result = Vec::new();
-    let is_file_dependency = |name: &str| name.starts_with("file");
-    let dependencies_per_type = vec![
-        &package_json.dependencies,
-        &package_json.dev_dependencies,
-        &package_json.optional_dependencies,
-    ];
-
-    for dependencies in dependencies_per_type.into_iter().flatten() {
-        for (dependency_name, version) in dependencies {
-            if is_file_dependency(dependency_name) {
-                debug!(
-                    "Dependency {} is using a local path as version.",
-                    dependency_name
-                );
-                continue;
-            }
+impl Timings {
+    fn record(&self, step: &str, duration: Duration) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((step.to_string(), duration));
+    }
 
-            let dependency_key = (resolver.resolve_dependency_key)(dependency_name, version);
-            if let Some(locked_dependency) = resolver.locked_dependencies.get(&dependency_key) {
-                if Version::parse(version).is_err() && &locked_dependency.version != version {
-                    debug!(
-                        "Dependency {} version is not pinned: {} -> {}.",
-                        dependency_name, version, locked_dependency.version
-                    );
-
-                    result.push(VersionToPin {
-                        dependency: dependency_name.clone(),
-                        package_version: version.clone(),
-                        locked_version: locked_dependency.version.clone(),
-                    });
-                } else {
-                    debug!("Dependency {} version is already pinned.", dependency_name);
-                }
-            } else {
-                debug!(
-                    "Dependency {} is unresolved in dependencies.",
-                    dependency_name
-                );
-            }
-        }
+    fn steps(&self) -> Vec<(String, Duration)> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
     }
+}
 
-    Ok(result)
+/// Number of steps of a run, package.json being written in a 7th one with `--update`.
+fn total_steps(args: &Args) -> usize {
+    if args.update {
+        7
+    } else {
+        6
+    }
 }
 
-#[derive(Debug, Parser)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-    #[command(flatten)]
-    verbose: Verbosity,
-    #[arg(short, long, default_value_t = false)]
-    update: bool,
+/// `[index/total]` prefix of the lines printed for a step.
+fn step_prefix(index: usize, total_steps: usize) -> String {
+    style(format!("[{index}/{total_steps}]"))
+        .bold()
+        .dim()
+        .to_string()
 }
 
 macro_rules! trace_fn {
-    ($index:expr, $total:expr, $icon:expr, $title:expr, $result:expr) => {{
-        let prefix = style(format!("[{}/{}]", $index, $total,))
-            .bold()
-            .dim()
-            .to_string();
+    ($timings:expr, $index:expr, $total:expr, $icon:expr, $title:expr, $result:expr) => {{
+        let prefix = step_prefix($index, $total);
         info!("{} [STARTED] {} {}...", prefix, $icon, $title);
+        let started_at = Instant::now();
         let result = $result;
+        $timings.record($title, started_at.elapsed());
         match &result {
             Ok(_) => {
                 info!("{} [SUCCESS] {} {}!", prefix, $icon, $title);
@@ -320,69 +387,367 @@ macro_rules! trace_fn {
     }};
 }
 
-fn write_pinned_versions(package_json: &mut Value, versions_to_pin: &Vec<VersionToPin>) {
-    fn update_dependencies(dependencies: Option<&mut Value>, versions_to_pin: &Vec<VersionToPin>) {
-        if let Some(dep_map) = dependencies {
-            for version_to_pin in versions_to_pin {
-                if let Some(locked_version) = dep_map.get_mut(&version_to_pin.dependency) {
-                    *locked_version = Value::String(version_to_pin.clone().locked_version);
-                }
-            }
+fn write_pinned_imports(deno_json: &mut Value, pin_plan: &PinPlan) {
+    let Some(imports) = deno_json.get_mut("imports").and_then(Value::as_object_mut) else {
+        return;
+    };
+
+    for specifier in imports.values_mut() {
+        let Some(name) = specifier
+            .as_str()
+            .and_then(parser::parse_npm_specifier)
+            .map(|(name, _)| name.to_string())
+        else {
+            continue;
+        };
+        if let Some(version_to_pin) = pin_plan
+            .versions_to_pin
+            .iter()
+            .find(|version_to_pin| version_to_pin.dependency == name)
+        {
+            *specifier = Value::String(format!(
+                "npm:{name}@{}",
+                pin_plan.pin_style.specifier(&version_to_pin.locked_version)
+            ));
         }
     }
-
-    update_dependencies(package_json.get_mut("dependencies"), versions_to_pin);
-    update_dependencies(package_json.get_mut("dev_dependencies"), versions_to_pin);
-    update_dependencies(
-        package_json.get_mut("optional_dependencies"),
-        versions_to_pin,
-    );
 }
 
+/// Writes `content` next to `path` first and renames it over `path`, so an interrupted write
+/// never leaves a truncated package.json behind.
 fn write_json_to_file(path: &PathBuf, indent: &Indent, content: &Value) -> Result<()> {
-    let mut buf = Vec::new();
-    let formatter = PrettyFormatter::with_indent(indent.indent().as_bytes());
-    let mut ser = serde_json::Serializer::with_formatter(&mut buf, formatter);
-    content
-        .serialize(&mut ser)
-        .expect("Failed to serialize JSON content");
-    buf.push(b'\n');
-
-    let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
-    let _ = file.write_all(buf.as_ref());
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} is not a file", path.display()))?;
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let written = write_temp_file(
+        &temp_path,
+        path,
+        &serialize_package_json(content, indent, "\n"),
+    )
+    .and_then(|()| std::fs::rename(&temp_path, path));
+    if written.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    Ok(written?)
+}
+
+fn write_temp_file(temp_path: &PathBuf, path: &PathBuf, content: &[u8]) -> std::io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(temp_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    if let Ok(metadata) = std::fs::metadata(path) {
+        file.set_permissions(metadata.permissions())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            // Only privileged users can give a file away, keep whatever owner we can get.
+            if let Err(err) =
+                std::os::unix::fs::chown(temp_path, Some(metadata.uid()), Some(metadata.gid()))
+            {
+                debug!("Unable to keep the owner of {}: {}.", path.display(), err);
+            }
+        }
+    }
     Ok(())
 }
 
+fn section_color(section: DependencySection) -> Color {
+    match section {
+        DependencySection::Dependencies => Color::Green,
+        DependencySection::DevDependencies => Color::Blue,
+        DependencySection::OptionalDependencies => Color::Yellow,
+    }
+}
+
+fn render_versions_to_pin(versions_to_pin: &[VersionToPin], pin_style: PinStyle) -> Vec<String> {
+    let mut sections: Vec<DependencySection> = versions_to_pin
+        .iter()
+        .map(|version_to_pin| version_to_pin.section)
+        .collect();
+    sections.sort();
+    sections.dedup();
+
+    let other_sections = |version_to_pin: &VersionToPin| -> Vec<String> {
+        versions_to_pin
+            .iter()
+            .filter(|other| {
+                other.dependency == version_to_pin.dependency
+                    && other.section != version_to_pin.section
+            })
+            .map(|other| other.section.to_string())
+            .collect()
+    };
+
+    let mut lines = Vec::new();
+    for section in sections {
+        let mut table = Table::new();
+        table.load_preset(presets::NOTHING);
+        for version_to_pin in versions_to_pin
+            .iter()
+            .filter(|version_to_pin| version_to_pin.section == section)
+        {
+            let mut row = vec![
+                version_to_pin.dependency.clone() + ":",
+                version_to_pin.package_version.clone(),
+                "→".to_string(),
+                pin_style.specifier(&version_to_pin.locked_version),
+            ];
+            let other_sections = other_sections(version_to_pin);
+            if !other_sections.is_empty() {
+                row.push(format!("(also in {})", other_sections.join(", ")));
+            }
+            table.add_row(row);
+        }
+
+        lines.push(
+            style(section.to_string())
+                .fg(section_color(section))
+                .bold()
+                .to_string(),
+        );
+        lines.extend(table.lines().map(|row| row.trim().to_string()));
+    }
+
+    lines
+}
+
+const UNSCOPED: &str = "(unscoped)";
+
+fn group_by_scope(versions_to_pin: &[VersionToPin]) -> BTreeMap<&str, Vec<VersionToPin>> {
+    let mut scopes: BTreeMap<&str, Vec<VersionToPin>> = BTreeMap::new();
+    for version_to_pin in versions_to_pin {
+        let scope = version_to_pin
+            .dependency
+            .split_once('/')
+            .map(|(scope, _)| scope)
+            .filter(|scope| scope.starts_with('@'))
+            .unwrap_or(UNSCOPED);
+        scopes
+            .entry(scope)
+            .or_default()
+            .push(version_to_pin.clone());
+    }
+
+    scopes
+}
+
+/// Same tables as [`render_versions_to_pin`], one per npm scope.
+fn render_versions_to_pin_by_scope(
+    versions_to_pin: &[VersionToPin],
+    pin_style: PinStyle,
+) -> Vec<String> {
+    group_by_scope(versions_to_pin)
+        .into_iter()
+        .flat_map(|(scope, versions_to_pin)| {
+            std::iter::once(style(scope).bold().underlined().to_string())
+                .chain(render_versions_to_pin(&versions_to_pin, pin_style))
+        })
+        .collect()
+}
+
+fn render_timings(steps: &[(String, Duration)]) -> Vec<String> {
+    let format_duration = |duration: Duration| format!("{:.1} ms", duration.as_secs_f64() * 1000.0);
+
+    let mut table = Table::new();
+    table.load_preset(presets::NOTHING);
+    for (step, duration) in steps {
+        table.add_row(vec![step.clone(), format_duration(*duration)]);
+    }
+    table.add_row(vec![
+        "Total".to_string(),
+        format_duration(steps.iter().map(|(_, duration)| *duration).sum()),
+    ]);
+
+    table.lines().map(|row| row.trim().to_string()).collect()
+}
+
+fn render_summary(stats: &PinStats, update: bool) -> String {
+    let per_section: Vec<String> = stats
+        .per_section
+        .iter()
+        .map(|(section, count)| format!("{count} {section}"))
+        .collect();
+
+    format!(
+        "{} dependencies {} ({})",
+        stats.total,
+        if update { "pinned" } else { "can be pinned" },
+        per_section.join(", ")
+    )
+}
+
+#[allow(clippy::too_many_lines)]
 fn generate_update_command_from_args(args: &Args) -> String {
     let mut update_command = vec!["npd"];
-    let mut hint = "-".to_string();
 
-    if args.verbose.is_silent() {
-        update_command.push("-q");
-    } else {
-        let level_value: i8 = match args.verbose.log_level() {
-            None => -1,
-            Some(log::Level::Error) => 0,
-            Some(log::Level::Warn) => 1,
-            Some(log::Level::Info) => 2,
-            Some(log::Level::Debug) => 3,
-            Some(log::Level::Trace) => 4,
-        };
+    let level_value: i8 = match args.verbose.log_level() {
+        None => -2,
+        Some(log::Level::Error) => -1,
+        Some(log::Level::Warn) => 0,
+        Some(log::Level::Info) => 1,
+        Some(log::Level::Debug) => 2,
+        Some(log::Level::Trace) => 3,
+    };
 
-        if level_value > 0 {
-            #[allow(clippy::cast_sign_loss)]
-            hint.push_str(&("v".repeat(level_value as usize)));
-            update_command.push(hint.as_str());
-        }
+    let hint = match level_value {
+        ..=-1 => "-".to_string() + &"q".repeat(level_value.unsigned_abs().into()),
+        0 => String::new(),
+        1.. => "-".to_string() + &"v".repeat(level_value.unsigned_abs().into()),
+    };
+    if !hint.is_empty() {
+        update_command.push(hint.as_str());
+    }
+
+    let cwd = args
+        .cwd
+        .as_ref()
+        .map(|cwd| format!("--cwd {}", cwd.display()));
+    if let Some(cwd) = &cwd {
+        update_command.push(cwd);
+    }
+
+    let manifest = args
+        .manifest
+        .as_ref()
+        .map(|manifest| format!("--manifest {}", manifest.display()));
+    if let Some(manifest) = &manifest {
+        update_command.push(manifest);
+    }
+
+    let lockfile = args
+        .lockfile
+        .as_ref()
+        .map(|lockfile| format!("--lockfile {}", lockfile.display()));
+    if let Some(lockfile) = &lockfile {
+        update_command.push(lockfile);
+    }
+
+    let package_manager = args
+        .package_manager
+        .map(|package_manager| format!("--package-manager {package_manager}"));
+    if let Some(package_manager) = &package_manager {
+        update_command.push(package_manager);
+    }
+
+    let max_depth = args
+        .max_depth
+        .map(|max_depth| format!("--max-depth {max_depth}"));
+    if let Some(max_depth) = &max_depth {
+        update_command.push(max_depth);
+    }
+
+    if args.stop_at_git_root {
+        update_command.push("--stop-at-git-root");
+    }
+
+    if args.allow_unknown_lockfile_version {
+        update_command.push("--allow-unknown-lockfile-version");
+    }
+
+    let max_lockfile_size = (args.max_lockfile_size != parser::DEFAULT_MAX_LOCK_FILE_SIZE)
+        .then(|| format!("--max-lockfile-size {}", args.max_lockfile_size));
+    if let Some(max_lockfile_size) = &max_lockfile_size {
+        update_command.push(max_lockfile_size);
+    }
+
+    let output = args
+        .output
+        .as_ref()
+        .map(|output| format!("-o {}", output.display()));
+    if let Some(output) = &output {
+        update_command.push(output);
+    }
+
+    if args.respect_overrides {
+        update_command.push("--respect-overrides");
+    }
+
+    if args.apply_resolutions {
+        update_command.push("--apply-resolutions");
+    }
+
+    if args.prod {
+        update_command.push("--prod");
+    }
+
+    if args.ignore_optional {
+        update_command.push("--ignore-optional");
+    }
+
+    if args.allow_downgrade {
+        update_command.push("--allow-downgrade");
+    }
+
+    let dev_scopes: Vec<String> = args
+        .dev_scope
+        .iter()
+        .map(|dev_scope| format!("--dev-scope '{dev_scope}'"))
+        .collect();
+    update_command.extend(dev_scopes.iter().map(String::as_str));
+
+    let ignores: Vec<String> = args
+        .ignore
+        .iter()
+        .map(|pattern| format!("--ignore '{pattern}'"))
+        .collect();
+    update_command.extend(ignores.iter().map(String::as_str));
+
+    let ignore_file = args
+        .ignore_file
+        .as_ref()
+        .map(|ignore_file| format!("--ignore-file {}", ignore_file.display()));
+    if let Some(ignore_file) = &ignore_file {
+        update_command.push(ignore_file);
+    }
+
+    let since = args.since.as_ref().map(|since| format!("--since {since}"));
+    if let Some(since) = &since {
+        update_command.push(since);
+    }
+
+    if args.strip_prefix {
+        update_command.push("--strip-prefix");
+    }
+
+    let pin_style =
+        (args.pin_style != PinStyle::Exact).then(|| format!("--pin-style {}", args.pin_style));
+    if let Some(pin_style) = &pin_style {
+        update_command.push(pin_style);
+    }
+
+    if args.dedupe_write {
+        update_command.push("--dedupe-write");
+    }
+
+    if args.workspaces {
+        update_command.push("--workspaces");
+    }
+
+    if args.member_locks {
+        update_command.push("--member-locks");
+    }
+
+    let threads = args.threads.map(|threads| format!("--threads {threads}"));
+    if let Some(threads) = &threads {
+        update_command.push(threads);
     }
 
     update_command.push("-u");
     update_command.join(" ")
 }
 
-#[allow(clippy::too_many_lines)]
-fn main() {
-    let args = Args::parse();
+fn main() -> ExitCode {
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
 
     let format = tracing_subscriber::fmt::format()
         .with_level(true)
@@ -390,133 +755,1107 @@ fn main() {
         .with_timer(tracing_subscriber::fmt::time::time())
         .compact();
 
-    tracing_subscriber::fmt()
-        .with_max_level(args.verbose.log_level_filter().as_trace())
-        .event_format(format)
+    // Logs go to stderr so stdout only carries results, which stay parseable with --format json.
+    // Escape codes are kept out of piped output such as CI logs.
+    let (level_filter, log_level) = reload::Layer::new(args.verbose.log_level_filter().as_trace());
+    tracing_subscriber::registry()
+        .with(level_filter)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_ansi(Term::stderr().is_term())
+                .event_format(format),
+        )
         .init();
 
-    let total_steps = if args.update { 7 } else { 6 };
+    let print_timings = args.timings && OutputMode::from(&args.verbose) != OutputMode::Silent;
+    let timings = Timings::default();
+    let set_log_level = |level| {
+        if let Err(err) = log_level.modify(|filter| *filter = level) {
+            warn!("Unable to change the log level: {err}");
+        }
+    };
+    let exit_code = match run(args, &matches, &timings, set_log_level) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            error!("{:#}", err);
+            if err.is::<EnginesNotSatisfied>() || err.is::<VersionsToPin>() {
+                ExitCode::from(EXIT_CHECK_FAILED)
+            } else {
+                ExitCode::from(EXIT_ERROR)
+            }
+        }
+    };
+
+    if print_timings {
+        for row in render_timings(&timings.steps()) {
+            eprintln!("[TIMINGS] {row}");
+        }
+    }
+
+    exit_code
+}
+
+#[allow(clippy::too_many_lines)]
+fn run(
+    mut args: Args,
+    matches: &ArgMatches,
+    timings: &Timings,
+    set_log_level: impl FnOnce(LevelFilter),
+) -> Result<()> {
+    if args.update && matches!(args.command, Some(Command::Check | Command::Engines)) {
+        return Err(anyhow!("Only the pin command updates package.json"));
+    }
+    if let Some(cwd) = &args.cwd {
+        std::env::set_current_dir(cwd).context("Unable to change the current directory")?;
+    }
+    if let Some(npdrc) = finder::get_npdrc(FindUpOptions::from(&args)) {
+        args.apply_config(
+            &parser::parse_npdrc(&npdrc)
+                .map_err(|err| anyhow!("Unable to parse .npdrc file: {err}"))?,
+            matches,
+        )?;
+    }
+    match args.command {
+        // An `.npdrc` setting `update` applies to `pin` only.
+        Some(Command::Check) => args.update = false,
+        Some(Command::Engines) => {
+            args.update = false;
+            args.check_engines = true;
+        }
+        Some(Command::Pin) | None => {}
+    }
+    // Machine readable formats imply `-q` unless a verbosity flag is given, so only errors are
+    // logged next to the JSON.
+    if args.format != OutputFormat::Table && !args.verbose.is_present() {
+        args.verbose = Verbosity::new(0, 1);
+        set_log_level(args.verbose.log_level_filter().as_trace());
+    }
+    // Read here rather than by clap, so that `--workspaces` ignores it instead of conflicting.
+    if args.manifest.is_none() && !args.workspaces {
+        args.manifest = std::env::var_os("PACKAGE_JSON")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
+    }
+    if let Some(ignore_file) = &args.ignore_file {
+        args.ignore_file_patterns = parser::parse_ignore_file(ignore_file).map_err(|err| {
+            anyhow!(
+                "Unable to read ignore file {}: {err}",
+                ignore_file.display()
+            )
+        })?;
+    }
+    if args.config_print {
+        if OutputMode::from(&args.verbose) != OutputMode::Silent {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&args)
+                    .context("Unable to serialize the effective options")?
+            );
+        }
+        return Ok(());
+    }
+
+    let is_stdin = |path: &Option<PathBuf>| {
+        path.as_ref()
+            .is_some_and(|path| path.as_os_str() == parser::STDIN_PATH)
+    };
+    if is_stdin(&args.manifest) && is_stdin(&args.lockfile) {
+        return Err(anyhow!(
+            "Only one of --manifest and --lockfile can be read from the standard input"
+        ));
+    }
+    if is_stdin(&args.manifest) && args.update && args.output.is_none() {
+        return Err(anyhow!(
+            "A package.json read from the standard input needs -o to be updated"
+        ));
+    }
+
+    let total_steps = total_steps(&args);
     let package = trace_fn!(
+        timings,
         1,
         total_steps,
         "📦",
         "Resolving package.json",
-        finder::get_package()
+        match &args.manifest {
+            Some(manifest) => finder::get_manifest(manifest),
+            None => finder::get_package(),
+        }
     )
-    .expect("Unable to get package.json file in the current directory");
+    .context("Unable to get package.json file in the current directory")?;
     let package_lock = trace_fn!(
+        timings,
         2,
         total_steps,
         "🔒",
         "Resolving lock file",
-        finder::get_most_recently_modified_lock()
+        match &args.lockfile {
+            Some(lockfile) => finder::get_lock(lockfile, args.package_manager),
+            None => finder::get_most_recently_modified_lock(FindUpOptions::from(&args)),
+        }
     )
-    .expect("Unable to get the most recently modified lock file in the current directory");
-    let (parsed_package, mut raw_package, indent) = trace_fn!(
+    .context("Unable to get the lock file")?;
+    let (parsed_package, raw_package, indent) = trace_fn!(
+        timings,
         3,
         total_steps,
         "📦",
         "Parsing package.json",
-        parser::parse_package(&package)
+        if parser::is_deno_manifest(&package) {
+            parser::parse_deno_manifest(&package)
+        } else {
+            parser::parse_package(&package)
+        }
     )
-    .expect("Unable to parse package.json file");
-    let parsed_lock_package = trace_fn!(
+    .map_err(|err| anyhow!("Unable to parse package.json file: {err}"))?;
+    if let Some(declared_package_manager) = parsed_package
+        .declared_package_manager()
+        .filter(|package_manager| *package_manager != package_lock.package_manager)
+    {
+        warn!(
+            "[WARN] package.json declares {} as its package manager but {} belongs to {}, consider removing the stale lock file.",
+            declared_package_manager,
+            package_lock.path.display(),
+            package_lock.package_manager
+        );
+    }
+    if finder::is_lock_older_than_package(&package, &package_lock.path) {
+        warn!(
+            "[WARN] {} is older than package.json, it may be stale: reinstall dependencies before pinning.",
+            package_lock.path.display()
+        );
+    }
+    for (dependency, sections) in parsed_package.dependencies_in_several_sections() {
+        warn!(
+            "[WARN] {} is declared in {}, keep it in a single section or pass --dedupe-write.",
+            dependency,
+            sections
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" and ")
+        );
+    }
+    if package_lock.package_manager == PackageManager::Yarn {
+        if let Some(yarnrc) = finder::get_yarnrc(FindUpOptions::from(&args)) {
+            match parser::parse_yarnrc(&yarnrc) {
+                Ok(yarnrc) => debug!("Yarn uses the {} linker.", yarnrc.node_linker()),
+                Err(err) => debug!("Unable to parse {}: {}.", yarnrc.display(), err),
+            }
+        }
+    }
+    let mut resolver_cache = ResolverCache::default()
+        .allow_unknown_lock_version(args.allow_unknown_lockfile_version)
+        .max_lock_file_size(args.max_lockfile_size);
+    let resolver = trace_fn!(
+        timings,
         4,
         total_steps,
         "🔒",
         "Parsing lock file",
-        parser::parse_lock(&package_lock)
+        resolver_cache.get_or_load(&package_lock)
     )
-    .expect("Unable to parse lock file");
-
-    let resolver = match parsed_lock_package {
-        PackageManagerLock::Npm(npm_lock) => npm_resolver(npm_lock),
-        PackageManagerLock::Yarn(yarn_lock) => yarn_resolver(yarn_lock),
-        PackageManagerLock::Pnpm(pnpm_lock) => pnpm_resolver(pnpm_lock),
+    .map_err(|err| anyhow!("Unable to parse lock file: {err}"))?;
+    let declares_dependencies = [
+        &parsed_package.dependencies,
+        &parsed_package.dev_dependencies,
+        &parsed_package.optional_dependencies,
+    ]
+    .into_iter()
+    .flatten()
+    .any(|dependencies| !dependencies.is_empty());
+    if declares_dependencies && resolver.locked_dependencies.is_empty() {
+        warn!(
+            "[WARN] {} is empty, run {} install to lock dependencies before pinning.",
+            package_lock.path.display(),
+            package_lock.package_manager
+        );
+    }
+    if args.print_resolver {
+        if OutputMode::from(&args.verbose) != OutputMode::Silent {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&resolver.locked_versions())
+                    .context("Unable to serialize the lock file entries")?
+            );
+        }
+        return Ok(());
+    }
+    if let Some(dependency) = &args.explain {
+        let explanations = explain_dependency(
+            &parsed_package,
+            &resolver,
+            &PinOptions::from(&args),
+            dependency,
+        );
+        match args.format {
+            OutputFormat::Table => {
+                if OutputMode::from(&args.verbose) != OutputMode::Silent {
+                    print_explanations(dependency, &explanations, total_steps);
+                }
+            }
+            format => {
+                for line in render_json(format, &explanations)
+                    .context("Unable to serialize the dependency explanation")?
+                {
+                    println!("{line}");
+                }
+            }
+        }
+        return Ok(());
+    }
+    if args.dump_unresolved {
+        if OutputMode::from(&args.verbose) != OutputMode::Silent {
+            for name in
+                unresolved_dependencies(&parsed_package, &resolver, &PinOptions::from(&args))
+            {
+                println!("{name}");
+            }
+        }
+        return Ok(());
+    }
+    if args.check_engines {
+        let lock = resolver_cache
+            .get_or_load_lock(&package_lock)
+            .map_err(|err| anyhow!("Unable to parse lock file: {err}"))?;
+        let node_version = match finder::get_node_version_file(FindUpOptions::from(&args)) {
+            Some(path) => parser::parse_node_version_file(&path)
+                .map_err(|err| anyhow!("Unable to parse {}: {err}", path.display()))?,
+            None => None,
+        };
+        let engine_checks = engines::with_node_version(
+            engines::project_engines(&parsed_package, &lock),
+            node_version,
+        )
+        .map(|project_engines| engines::check_engines(&project_engines, &resolver));
+        let engine_strict = match finder::get_npmrc(FindUpOptions::from(&args)) {
+            Some(path) => {
+                parser::parse_npmrc(&path)
+                    .map_err(|err| anyhow!("Unable to parse {}: {err}", path.display()))?
+                    .engine_strict
+            }
+            None => false,
+        };
+        let violations = engine_checks
+            .iter()
+            .flatten()
+            .filter(|engine_check| !engine_check.satisfied)
+            .count();
+        match args.format {
+            OutputFormat::Table => {
+                if OutputMode::from(&args.verbose) != OutputMode::Silent {
+                    print_engine_checks(engine_checks.as_deref(), total_steps);
+                }
+            }
+            format => {
+                for line in render_json(format, &engine_checks.unwrap_or_default())
+                    .context("Unable to serialize the engines report")?
+                {
+                    println!("{line}");
+                }
+            }
+        }
+        if engine_strict && violations > 0 {
+            return Err(EnginesNotSatisfied(violations).into());
+        }
+        return Ok(());
+    }
+    let duplicate_versions = if args.dedupe_report {
+        let lock = resolver_cache
+            .get_or_load_lock(&package_lock)
+            .map_err(|err| anyhow!("Unable to parse lock file: {err}"))?;
+        Some(dedupe::find_duplicate_versions(&parsed_package, &lock))
+    } else {
+        None
     };
 
-    let versions_to_pin = trace_fn!(
+    let mut packages_pins = trace_fn!(
+        timings,
         5,
         total_steps,
         "⚙️",
         "Computing dependency versions to pin",
-        compute_versions_to_pin(&parsed_package, &resolver)
+        plan_packages_pins(
+            &args,
+            package,
+            parsed_package,
+            raw_package,
+            indent,
+            &resolver,
+            &mut resolver_cache
+        )
     )
-    .unwrap();
+    .context("Unable to compute dependency versions to pin")?;
+    for version_to_pin in packages_pins
+        .iter()
+        .flat_map(|package_pins| &package_pins.pin_plan.skipped_downgrades)
+    {
+        warn!(
+            "[WARN] Skipping {} in {}: {} is older than {}, pass --allow-downgrade to pin it anyway.",
+            version_to_pin.dependency,
+            version_to_pin.section,
+            version_to_pin.locked_version,
+            version_to_pin.package_version
+        );
+    }
 
-    if args.verbose.is_silent() {
-        return;
+    if let Some(report_file) = &args.report_file {
+        write_report(report_file, &packages_pins)
+            .with_context(|| format!("Unable to write the report to {}", report_file.display()))?;
     }
 
-    let mut table = Table::new();
-    table.load_preset(presets::NOTHING);
-    for version_to_pin in versions_to_pin.clone() {
-        table.add_row(vec![
-            version_to_pin.dependency + ":",
-            version_to_pin.package_version,
-            "→".to_string(),
-            version_to_pin.locked_version,
-        ]);
+    match args.format {
+        OutputFormat::Table => {
+            if OutputMode::from(&args.verbose) != OutputMode::Silent {
+                print_results(&args, &packages_pins, total_steps);
+                if let Some(duplicate_versions) = &duplicate_versions {
+                    print_dedupe_report(duplicate_versions, total_steps);
+                }
+            }
+        }
+        format => {
+            for line in render_pins(format, &packages_pins)
+                .context("Unable to serialize the versions to pin")?
+            {
+                println!("{line}");
+            }
+        }
     }
 
-    let total_steps_str = style(format!("[{}/{}]", 6, total_steps))
-        .bold()
-        .dim()
-        .to_string();
+    if args.command == Some(Command::Check) {
+        let versions_to_pin: usize = packages_pins
+            .iter()
+            .map(|package_pins| package_pins.pin_plan.versions_to_pin.len())
+            .sum();
+        if versions_to_pin > 0 {
+            return Err(VersionsToPin(versions_to_pin).into());
+        }
+    }
+    if !args.update {
+        return Ok(());
+    }
+
+    trace_fn!(
+        timings,
+        7,
+        total_steps,
+        "💾",
+        "Updating package.json",
+        packages_pins.iter_mut().try_for_each(|package_pins| {
+            let mut changed = !package_pins.pin_plan.is_empty();
+            if parser::is_deno_manifest(&package_pins.path) {
+                write_pinned_imports(&mut package_pins.raw_package, &package_pins.pin_plan);
+            } else {
+                apply_pins(&mut package_pins.raw_package, &package_pins.pin_plan);
+                if args.dedupe_write {
+                    for (dependency, section) in
+                        collapse_duplicate_sections(&mut package_pins.raw_package)
+                    {
+                        info!(
+                            "Removing {} from {} of {}.",
+                            dependency,
+                            section,
+                            package_pins.path.display()
+                        );
+                        changed = true;
+                    }
+                }
+            }
+            let output = args.output.as_ref().unwrap_or(&package_pins.path);
+            // `--output` gets written even when nothing changed, it would be missing otherwise.
+            if !changed && *output == package_pins.path {
+                return Ok(());
+            }
+            write_json_to_file(output, &package_pins.indent, &package_pins.raw_package)
+        })
+    )
+    .context("Failed to update package.json content")
+}
+
+fn pins_report(packages_pins: &[PackagePins]) -> Vec<Value> {
+    packages_pins
+        .iter()
+        .map(|package_pins| {
+            serde_json::json!({
+                "name": package_pins.name,
+                "path": package_pins.path,
+                "lockfileVersion": package_pins.lockfile_version,
+                "versionsToPin": package_pins.pin_plan.versions_to_pin,
+                "stats": package_pins.pin_plan.stats(),
+            })
+        })
+        .collect()
+}
+
+/// Writes the pin plan of every package as a JSON array, whether or not package.json gets updated.
+fn write_report(path: &PathBuf, packages_pins: &[PackagePins]) -> Result<()> {
+    std::fs::write(
+        path,
+        serde_json::to_string_pretty(&pins_report(packages_pins))? + "\n",
+    )?;
+    Ok(())
+}
+
+/// Renders `items` as a pretty JSON array, or as one compact JSON object per line.
+fn render_json<T: Serialize>(format: OutputFormat, items: &[T]) -> Result<Vec<String>> {
+    Ok(match format {
+        OutputFormat::Ndjson => items
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<_, _>>()?,
+        OutputFormat::Table | OutputFormat::Json => vec![serde_json::to_string_pretty(items)?],
+    })
+}
+
+/// Machine readable versions to pin: the per-package report as a JSON array, or every version to
+/// pin on its own line, tagged with the name of its package.
+fn render_pins(format: OutputFormat, packages_pins: &[PackagePins]) -> Result<Vec<String>> {
+    if format != OutputFormat::Ndjson {
+        return render_json(format, &pins_report(packages_pins));
+    }
 
-    if table.is_empty() {
-        info!(
-            "{} [RESULTS] {}{}",
-            total_steps_str,
-            "All dependency versions are already pinned ",
-            style(":)").green().to_string()
+    let versions_to_pin: Vec<Value> = packages_pins
+        .iter()
+        .flat_map(|package_pins| {
+            package_pins
+                .pin_plan
+                .versions_to_pin
+                .iter()
+                .map(|version_to_pin| {
+                    let mut version_to_pin = serde_json::to_value(version_to_pin)?;
+                    version_to_pin["package"] = Value::String(package_pins.name.clone());
+                    Ok(version_to_pin)
+                })
+        })
+        .collect::<Result<_>>()?;
+    render_json(format, &versions_to_pin)
+}
+
+fn plan_packages_pins(
+    args: &Args,
+    package: PathBuf,
+    parsed_package: PackageJson,
+    raw_package: Value,
+    indent: Indent,
+    resolver: &DependencyVersionResolver,
+    resolver_cache: &mut ResolverCache,
+) -> Result<Vec<PackagePins>> {
+    let mut options = PinOptions::from(args);
+    if let Some(git_ref) = &args.since {
+        options.only_dependencies =
+            git::changed_dependencies_since(&package, &parsed_package, git_ref)?;
+        if options.only_dependencies.is_none() {
+            warn!(
+                "[WARN] {} is not in a git repository, --since {} is ignored.",
+                package.display(),
+                git_ref
+            );
+        }
+    }
+    let root = match package.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let members = if args.workspaces {
+        workspace::find_members(
+            &root,
+            parsed_package
+                .workspaces
+                .as_ref()
+                .map(Workspaces::packages)
+                .unwrap_or_default(),
+        )
+    } else {
+        Vec::new()
+    };
+
+    let mut packages_pins = vec![PackagePins {
+        pin_plan: compute_pin_plan(&parsed_package, resolver, &options)?,
+        path: package,
+        name: parsed_package.name,
+        raw_package,
+        indent,
+        lockfile_version: resolver.lockfile_version.clone(),
+    }];
+
+    if !members.is_empty() {
+        let threads = args
+            .threads
+            .or_else(|| std::thread::available_parallelism().ok())
+            .unwrap_or(NonZeroUsize::MIN);
+        if args.member_locks {
+            let resolvers = workspace::load_members_resolvers(
+                &members,
+                FindUpOptions::from(args),
+                resolver_cache,
+            )?;
+            packages_pins.extend(workspace::plan_members_pins_with_resolvers(
+                &members, &resolvers, &options, threads,
+            )?);
+        } else {
+            packages_pins.extend(workspace::plan_members_pins(
+                &members, resolver, &options, threads,
+            )?);
+        }
+    }
+
+    Ok(packages_pins)
+}
+
+fn print_results(args: &Args, packages_pins: &[PackagePins], total_steps: usize) {
+    let total_steps_str = step_prefix(6, total_steps);
+
+    let packages_to_pin: Vec<&PackagePins> = packages_pins
+        .iter()
+        .filter(|package_pins| !package_pins.pin_plan.is_empty())
+        .collect();
+
+    if packages_to_pin.is_empty() {
+        println!(
+            "{total_steps_str} [RESULTS] All dependency versions are already pinned {}",
+            style(":)").green()
         );
         return;
     }
 
-    info!(
-        "{} [RESULTS] {}",
-        total_steps_str,
-        if args.update {
-            "Dependency versions pinned"
-        } else {
-            "Dependency versions that can be pinned"
+    if !args.summary_only {
+        println!(
+            "{total_steps_str} [RESULTS] {}",
+            if args.update {
+                "Dependency versions pinned"
+            } else {
+                "Dependency versions that can be pinned"
+            }
+        );
+    }
+
+    for package_pins in packages_to_pin {
+        if args.workspaces {
+            let path = package_pins
+                .path
+                .strip_prefix(".")
+                .unwrap_or(&package_pins.path);
+            println!(
+                "{total_steps_str} [RESULTS] {}",
+                style(format!("{} ({})", package_pins.name, path.display())).bold()
+            );
         }
-    );
 
-    for row in table.lines() {
-        info!("{} [RESULTS] {}", total_steps_str, row.trim());
+        if args.summary_only {
+            println!(
+                "{total_steps_str} [RESULTS] {}",
+                render_summary(&package_pins.pin_plan.stats(), args.update)
+            );
+        } else if args.group_by_scope {
+            for row in render_versions_to_pin_by_scope(
+                &package_pins.pin_plan.versions_to_pin,
+                package_pins.pin_plan.pin_style,
+            ) {
+                println!("{total_steps_str} [RESULTS] {row}");
+            }
+        } else {
+            for row in render_versions_to_pin(
+                &package_pins.pin_plan.versions_to_pin,
+                package_pins.pin_plan.pin_style,
+            ) {
+                println!("{total_steps_str} [RESULTS] {row}");
+            }
+        }
     }
 
     if !args.update {
-        info!(
-            "{} [RESULTS] {}",
-            total_steps_str,
-            format!(
-                "Run {} to upgrade package.json.",
-                style(generate_update_command_from_args(&args))
-                    .bold()
-                    .cyan()
-            )
+        println!(
+            "{total_steps_str} [RESULTS] Run {} to upgrade package.json.",
+            style(generate_update_command_from_args(args)).bold().cyan()
+        );
+    }
+}
+
+fn print_engine_checks(engine_checks: Option<&[EngineCheck]>, total_steps: usize) {
+    let total_steps_str = step_prefix(6, total_steps);
+
+    let Some(engine_checks) = engine_checks else {
+        println!("{total_steps_str} [RESULTS] The project declares no engines to check against");
+        return;
+    };
+
+    let violations: Vec<&EngineCheck> = engine_checks
+        .iter()
+        .filter(|engine_check| !engine_check.satisfied)
+        .collect();
+    if violations.is_empty() {
+        println!(
+            "{total_steps_str} [RESULTS] All dependency engines are satisfied {}",
+            style(":)").green()
         );
         return;
     }
 
-    write_pinned_versions(&mut raw_package, &versions_to_pin);
-    trace_fn!(
-        7,
-        total_steps,
-        "💾",
-        "Updating package.json",
-        write_json_to_file(&package, &indent, &raw_package)
-    )
-    .expect("Failed to update package.json content");
+    println!("{total_steps_str} [RESULTS] Dependency engines not satisfied by the project");
+    for violation in violations {
+        println!(
+            "{total_steps_str} [RESULTS] {} requires {} {}, the project allows {}",
+            style(&violation.dependency).bold(),
+            violation.field,
+            violation.required,
+            violation.project
+        );
+    }
+}
+
+fn print_explanations(
+    dependency: &str,
+    explanations: &[DependencyExplanation],
+    total_steps: usize,
+) {
+    let total_steps_str = step_prefix(6, total_steps);
+
+    if explanations.is_empty() {
+        println!(
+            "{total_steps_str} [RESULTS] {} is not declared in package.json",
+            style(dependency).bold()
+        );
+        return;
+    }
+
+    for explanation in explanations {
+        let declared = format!("{} in {}", style(dependency).bold(), explanation.section);
+        let specifier = explanation.specifier.as_str().map_or_else(
+            || serde_json::to_string(&explanation.specifier).unwrap_or_default(),
+            ToString::to_string,
+        );
+        println!("{total_steps_str} [RESULTS] {declared} is declared as {specifier}");
+        match &explanation.locked_version {
+            Some(locked_version) => println!(
+                "{total_steps_str} [RESULTS] {declared} is looked up as {}, locked at {locked_version}",
+                explanation.resolver_key
+            ),
+            None => println!(
+                "{total_steps_str} [RESULTS] {declared} is looked up as {}, not found in the lock file",
+                explanation.resolver_key
+            ),
+        }
+        println!(
+            "{total_steps_str} [RESULTS] {declared}: {}",
+            explanation.decision
+        );
+    }
+}
+
+fn print_dedupe_report(duplicate_versions: &LockedVersions, total_steps: usize) {
+    let total_steps_str = step_prefix(6, total_steps);
+
+    if duplicate_versions.is_empty() {
+        println!("{total_steps_str} [RESULTS] No direct dependency is locked at several versions");
+        return;
+    }
+
+    println!("{total_steps_str} [RESULTS] Direct dependencies locked at several versions");
+    for (name, versions) in duplicate_versions {
+        println!(
+            "{total_steps_str} [RESULTS] {}: {}",
+            style(name).bold(),
+            versions
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use clap_verbosity_flag::Verbosity;
+    use riri_node_tools::pin::plan_pins;
+    use riri_node_tools::types::LockFileResult;
+
+    fn fixture_path(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    fn args_with_config(command_line: &[&str], config: &str) -> Args {
+        let config: NpdConfig = serde_json::from_str(config).expect("Unable to parse config");
+        let matches = Args::command().get_matches_from(command_line);
+        let mut args = Args::from_arg_matches(&matches).expect("Unable to parse arguments");
+        args.apply_config(&config, &matches)
+            .expect("Unable to apply config");
+
+        args
+    }
+
+    #[test]
+    fn cli_flags_take_precedence_over_config() {
+        let config = r#"{ "update": false }"#;
+        assert!(args_with_config(&["npd", "--update"], config).update);
+        assert!(!args_with_config(&["npd"], config).update);
+
+        let config = r#"{ "update": true }"#;
+        assert!(args_with_config(&["npd"], config).update);
+        assert!(!args_with_config(&["npd", "--no-update"], config).update);
+        assert!(!args_with_config(&["npd", "--update", "--no-update"], config).update);
+        assert!(args_with_config(&["npd", "pin", "--no-update", "--update"], config).update);
+    }
+
+    #[test]
+    fn config_fills_in_options_left_unset() {
+        let config = r#"{
+            "pinStyle": "tilde",
+            "ignore": ["@types/*"],
+            "format": "json",
+            "maxDepth": 2,
+            "prod": true
+        }"#;
+
+        let args = args_with_config(&["npd"], config);
+        assert_eq!(args.pin_style, PinStyle::Tilde);
+        assert_eq!(args.ignore, ["@types/*"]);
+        assert_eq!(args.format, OutputFormat::Json);
+        assert_eq!(args.max_depth, Some(2));
+        assert!(args.prod);
+
+        let args = args_with_config(
+            &[
+                "npd",
+                "check",
+                "--pin-style",
+                "exact",
+                "--ignore",
+                "lodash",
+                "--format",
+                "table",
+                "--max-depth",
+                "5",
+            ],
+            config,
+        );
+        assert_eq!(args.pin_style, PinStyle::Exact);
+        assert_eq!(args.ignore, ["lodash"]);
+        assert_eq!(args.format, OutputFormat::Table);
+        assert_eq!(args.max_depth, Some(5));
+        assert!(args.prod);
+    }
+
+    #[test]
+    fn config_rejects_unknown_keys_and_formats() {
+        assert!(serde_json::from_str::<NpdConfig>(r#"{ "pinstyle": "tilde" }"#).is_err());
+
+        let config: NpdConfig =
+            serde_json::from_str(r#"{ "format": "yaml" }"#).expect("Unable to parse config");
+        let matches = Args::command().get_matches_from(["npd"]);
+        let mut args = Args::from_arg_matches(&matches).expect("Unable to parse arguments");
+        assert!(args.apply_config(&config, &matches).is_err());
+    }
+
+    fn pin(section: DependencySection, dependency: &str, package_version: &str) -> VersionToPin {
+        VersionToPin {
+            section,
+            dependency: dependency.to_string(),
+            package_version: package_version.to_string(),
+            locked_version: "1.0.0".to_string(),
+        }
+    }
+
+    #[test]
+    fn render_versions_to_pin_grouped_by_section() {
+        let versions_to_pin = vec![
+            pin(DependencySection::DevDependencies, "eslint", "^1.0.0"),
+            pin(DependencySection::Dependencies, "lodash", "~1.0.0"),
+            pin(DependencySection::DevDependencies, "prettier", "^1.0.0"),
+        ];
+
+        assert_eq!(
+            render_versions_to_pin(&versions_to_pin, PinStyle::Exact),
+            vec![
+                "dependencies",
+                "lodash:  ~1.0.0  →  1.0.0",
+                "devDependencies",
+                "eslint:    ^1.0.0  →  1.0.0",
+                "prettier:  ^1.0.0  →  1.0.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn render_versions_to_pin_flags_duplicates() {
+        let versions_to_pin = vec![
+            pin(DependencySection::Dependencies, "lodash", "^1.0.0"),
+            pin(DependencySection::Dependencies, "react", "^1.0.0"),
+            pin(DependencySection::DevDependencies, "lodash", "^1.0.0"),
+        ];
+
+        assert_eq!(
+            render_versions_to_pin(&versions_to_pin, PinStyle::Exact),
+            vec![
+                "dependencies",
+                "lodash:  ^1.0.0  →  1.0.0  (also in devDependencies)",
+                "react:   ^1.0.0  →  1.0.0",
+                "devDependencies",
+                "lodash:  ^1.0.0  →  1.0.0  (also in dependencies)",
+            ]
+        );
+    }
+
+    #[test]
+    fn sections_have_distinct_colors() {
+        let colors = [
+            DependencySection::Dependencies,
+            DependencySection::DevDependencies,
+            DependencySection::OptionalDependencies,
+        ]
+        .map(section_color);
+
+        assert_ne!(colors[0], colors[1]);
+        assert_ne!(colors[0], colors[2]);
+        assert_ne!(colors[1], colors[2]);
+    }
+
+    #[test]
+    fn group_versions_to_pin_by_scope() {
+        let versions_to_pin = vec![
+            pin(DependencySection::DevDependencies, "@types/node", "^1.0.0"),
+            pin(DependencySection::Dependencies, "lodash", "^1.0.0"),
+            pin(
+                DependencySection::DevDependencies,
+                "@types/lodash",
+                "^1.0.0",
+            ),
+            pin(DependencySection::Dependencies, "@acme/ui", "^1.0.0"),
+            pin(DependencySection::DevDependencies, "prettier", "^1.0.0"),
+        ];
+
+        let scopes: Vec<(&str, Vec<String>)> = group_by_scope(&versions_to_pin)
+            .into_iter()
+            .map(|(scope, versions_to_pin)| {
+                (
+                    scope,
+                    versions_to_pin
+                        .into_iter()
+                        .map(|version_to_pin| version_to_pin.dependency)
+                        .collect(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            scopes,
+            vec![
+                (UNSCOPED, vec!["lodash".to_string(), "prettier".to_string()]),
+                ("@acme", vec!["@acme/ui".to_string()]),
+                (
+                    "@types",
+                    vec!["@types/node".to_string(), "@types/lodash".to_string()]
+                ),
+            ]
+        );
+        assert_eq!(
+            render_versions_to_pin_by_scope(&versions_to_pin[..2], PinStyle::Exact),
+            vec![
+                "(unscoped)",
+                "dependencies",
+                "lodash:  ^1.0.0  →  1.0.0",
+                "@types",
+                "devDependencies",
+                "@types/node:  ^1.0.0  →  1.0.0",
+            ]
+        );
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("riri-node-tools-{name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("Unable to create temporary directory");
+        dir
+    }
+
+    #[test]
+    fn write_pinned_package_json_to_output() {
+        let package = fixture_path("npm-v3-engines").join("package.json");
+        let original = std::fs::read_to_string(&package).expect("Unable to read fixture");
+        let (package_json, mut raw_package, indent) =
+            parser::parse_package(&package).expect("Unable to parse package.json fixture");
+        let lock = parser::parse_lock(
+            &LockFileResult {
+                path: fixture_path("npm-v3-engines").join("package-lock.json"),
+                package_manager: PackageManager::Npm,
+            },
+            parser::DEFAULT_MAX_LOCK_FILE_SIZE,
+        )
+        .expect("Unable to parse lock file fixture");
+        let pin_plan =
+            plan_pins(&package_json, &lock).expect("Unable to compute dependency versions to pin");
+        let output = temp_dir("write-output").join("package.json");
+
+        apply_pins(&mut raw_package, &pin_plan);
+        write_json_to_file(&output, &indent, &raw_package).expect("Unable to write output");
+
+        assert_eq!(
+            std::fs::read_to_string(&package).expect("Unable to read fixture"),
+            original
+        );
+        assert!(std::fs::read_to_string(&output)
+            .expect("Unable to read output")
+            .contains(r#""lodash": "4.17.21""#));
+    }
+
+    #[test]
+    fn write_package_json_atomically() {
+        let dir = temp_dir("write-atomic");
+        let output = dir.join("package.json");
+        let previous = "{\n  \"name\": \"previous\"\n}\n";
+        std::fs::write(&output, previous).expect("Unable to write package.json");
+
+        write_json_to_file(
+            &output,
+            &detect_indent::detect_indent(previous),
+            &serde_json::json!({ "name": "atomic" }),
+        )
+        .expect("Unable to write output");
+
+        assert_eq!(
+            std::fs::read_to_string(&output).expect("Unable to read output"),
+            "{\n  \"name\": \"atomic\"\n}\n"
+        );
+        let files: Vec<_> = std::fs::read_dir(&dir)
+            .expect("Unable to read temporary directory")
+            .filter_map(Result::ok)
+            .map(|entry| entry.file_name())
+            .collect();
+        assert_eq!(files, vec!["package.json"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_package_json_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let output = temp_dir("write-permissions").join("package.json");
+        std::fs::write(&output, "{}\n").expect("Unable to write package.json");
+        std::fs::set_permissions(&output, std::fs::Permissions::from_mode(0o640))
+            .expect("Unable to set permissions");
+
+        write_json_to_file(
+            &output,
+            &detect_indent::detect_indent("{}"),
+            &serde_json::json!({ "name": "restricted" }),
+        )
+        .expect("Unable to write output");
+
+        assert_eq!(
+            std::fs::metadata(&output)
+                .expect("Unable to read output metadata")
+                .permissions()
+                .mode()
+                & 0o777,
+            0o640
+        );
+    }
+
+    #[test]
+    fn write_minified_package_json_with_two_spaces() {
+        let output = temp_dir("write-minified").join("package.json");
+
+        write_json_to_file(
+            &output,
+            &detect_indent::detect_indent(r#"{"name":"minified"}"#),
+            &serde_json::json!({ "name": "minified" }),
+        )
+        .expect("Unable to write output");
+
+        assert_eq!(
+            std::fs::read_to_string(&output).expect("Unable to read output"),
+            "{\n  \"name\": \"minified\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn render_timings_lists_every_step() {
+        let timings = Timings::default();
+        timings.record("Resolving package.json", Duration::from_micros(1500));
+        timings.record("Parsing lock file", Duration::from_millis(12));
+
+        assert_eq!(
+            render_timings(&timings.steps()),
+            vec![
+                "Resolving package.json  1.5 ms",
+                "Parsing lock file       12.0 ms",
+                "Total                   13.5 ms",
+            ]
+        );
+    }
+
+    #[test]
+    fn render_summary_counts_per_section() {
+        let fixture = fixture_path("npm-sections");
+        let (package_json, _, _) = parser::parse_package(&fixture.join("package.json"))
+            .expect("Unable to parse package.json fixture");
+        let lock = parser::parse_lock(
+            &LockFileResult {
+                path: fixture.join("package-lock.json"),
+                package_manager: PackageManager::Npm,
+            },
+            parser::DEFAULT_MAX_LOCK_FILE_SIZE,
+        )
+        .expect("Unable to parse lock file fixture");
+        let stats = plan_pins(&package_json, &lock)
+            .expect("Unable to compute dependency versions to pin")
+            .stats();
+
+        assert_eq!(
+            render_summary(&stats, false),
+            "4 dependencies can be pinned (1 dependencies, 2 devDependencies, 1 optionalDependencies)"
+        );
+        assert!(render_summary(&stats, true).starts_with("4 dependencies pinned ("));
+    }
+
+    #[test]
+    fn output_mode_per_verbosity() {
+        let tests = [
+            // verbose, quiet, expected_mode
+            (0, 0, OutputMode::Results),
+            (0, 1, OutputMode::Results),
+            (0, 2, OutputMode::Silent),
+            (0, 255, OutputMode::Silent),
+            (1, 0, OutputMode::Progress),
+            (3, 0, OutputMode::Progress),
+            (1, 1, OutputMode::Results),
+        ];
+
+        for (verbose, quiet, expected_mode) in tests {
+            assert_eq!(
+                OutputMode::from(&Verbosity::<WarnLevel>::new(verbose, quiet)),
+                expected_mode,
+                "verbose = {verbose}, quiet = {quiet}"
+            );
+        }
+    }
+
+    #[test]
+    fn step_prefixes_share_the_total_steps() {
+        let prefixes = |args: &Args| -> Vec<String> {
+            (1..=total_steps(args))
+                .map(|index| {
+                    console::strip_ansi_codes(&step_prefix(index, total_steps(args))).to_string()
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            prefixes(&Args::parse_from(["npd"])),
+            ["[1/6]", "[2/6]", "[3/6]", "[4/6]", "[5/6]", "[6/6]"]
+        );
+        assert_eq!(
+            prefixes(&Args::parse_from(["npd", "--update"])),
+            ["[1/7]", "[2/7]", "[3/7]", "[4/7]", "[5/7]", "[6/7]", "[7/7]"]
+        );
+
+        let args = args_with_config(&["npd"], r#"{ "update": true }"#);
+        assert_eq!(total_steps(&args), 7);
+    }
 
     #[test]
     fn generate_update_command() {
@@ -526,19 +1865,18 @@ mod tests {
             (1, 0, "npd -v -u"),
             (2, 0, "npd -vv -u"),
             (3, 0, "npd -vvv -u"),
-            (4, 0, "npd -vvvv -u"),
-            (5, 0, "npd -vvvv -u"),
-            (255, 0, "npd -vvvv -u"),
+            (4, 0, "npd -vvv -u"),
+            (255, 0, "npd -vvv -u"),
             (0, 1, "npd -q -u"),
-            (0, 2, "npd -q -u"),
-            (0, 255, "npd -q -u"),
+            (0, 2, "npd -qq -u"),
+            (0, 255, "npd -qq -u"),
             (255, 255, "npd -u"),
         ];
 
         for (verbose, quiet, expected_command) in &tests {
             let args = Args {
                 verbose: Verbosity::new(*verbose, *quiet),
-                update: false,
+                ..Args::parse_from(["npd"])
             };
             assert_eq!(
                 generate_update_command_from_args(&args),
@@ -546,5 +1884,29 @@ mod tests {
                 "verbose = {verbose}, quiet = {quiet}, expected = {expected_command}"
             );
         }
+
+        let args = Args::parse_from(["npd", "--output", "pinned/package.json"]);
+        assert_eq!(
+            generate_update_command_from_args(&args),
+            "npd -o pinned/package.json -u"
+        );
+
+        let args = Args::parse_from([
+            "npd",
+            "--lockfile",
+            "locks/npm.json",
+            "--package-manager",
+            "npm",
+        ]);
+        assert_eq!(
+            generate_update_command_from_args(&args),
+            "npd --lockfile locks/npm.json --package-manager npm -u"
+        );
+
+        let args = Args::parse_from(["npd", "--ignore", "@types/*", "--ignore-file", ".pinignore"]);
+        assert_eq!(
+            generate_update_command_from_args(&args),
+            "npd --ignore '@types/*' --ignore-file .pinignore -u"
+        );
     }
 }
@@ -0,0 +1,1106 @@
+use crate::engines::lowest_allowed_version;
+use crate::resolver::{build_resolver, DependencyVersionResolver};
+use crate::types::{
+    BundleDependencies, DependencySection, DependencySpecifier, PackageJson, PackageManagerLock,
+};
+use detect_indent::Indent;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use serde_json::ser::PrettyFormatter;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::Error;
+use std::str::FromStr;
+use tracing::debug;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionToPin {
+    pub section: DependencySection,
+    pub dependency: String,
+    pub package_version: String,
+    pub locked_version: String,
+}
+
+impl VersionToPin {
+    /// Whether the locked version is older than the lowest version the declared range allows.
+    pub fn is_downgrade(&self) -> bool {
+        match (
+            Version::parse(&self.locked_version),
+            lowest_allowed_version(&self.package_version),
+        ) {
+            (Ok(locked_version), Some(lowest_version)) => locked_version < lowest_version,
+            _ => false,
+        }
+    }
+}
+
+/// How a locked version gets written: `1.2.3`, `~1.2.3` or `^1.2.3`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PinStyle {
+    #[default]
+    Exact,
+    Tilde,
+    Caret,
+}
+
+impl PinStyle {
+    pub fn specifier(self, locked_version: &str) -> String {
+        match self {
+            PinStyle::Exact => locked_version.to_string(),
+            PinStyle::Tilde => format!("~{locked_version}"),
+            PinStyle::Caret => format!("^{locked_version}"),
+        }
+    }
+}
+
+impl fmt::Display for PinStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PinStyle::Exact => "exact",
+            PinStyle::Tilde => "tilde",
+            PinStyle::Caret => "caret",
+        })
+    }
+}
+
+impl FromStr for PinStyle {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "exact" => Ok(PinStyle::Exact),
+            "tilde" => Ok(PinStyle::Tilde),
+            "caret" => Ok(PinStyle::Caret),
+            _ => Err(format!(
+                "unknown pin style {value:?}, expected one of exact, tilde or caret"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct PinOptions {
+    pub respect_overrides: bool,
+    /// Pin direct dependencies forced by yarn `resolutions` to the resolution version.
+    pub apply_resolutions: bool,
+    pub prod: bool,
+    pub ignore_optional: bool,
+    pub allow_downgrade: bool,
+    /// Globs restricting which devDependencies get pinned, all of them when empty.
+    pub dev_scopes: Vec<String>,
+    /// Globs of dependencies never pinned, whatever their section.
+    pub ignore: Vec<String>,
+    /// Consider `^1.2.3`, `~1.2.3` or `>=1.2.3` already pinned when `1.2.3` is locked.
+    pub strip_prefix: bool,
+    pub pin_style: PinStyle,
+    /// Restricts pinning to these dependencies, all of them when `None`.
+    pub only_dependencies: Option<BTreeSet<String>>,
+}
+
+impl PinOptions {
+    fn includes(&self, section: DependencySection) -> bool {
+        match section {
+            DependencySection::Dependencies => true,
+            DependencySection::DevDependencies => !self.prod,
+            DependencySection::OptionalDependencies => !self.prod && !self.ignore_optional,
+        }
+    }
+
+    fn includes_dependency(&self, section: DependencySection, dependency_name: &str) -> bool {
+        section != DependencySection::DevDependencies
+            || self.dev_scopes.is_empty()
+            || self
+                .dev_scopes
+                .iter()
+                .any(|dev_scope| matches_glob(dev_scope, dependency_name))
+    }
+
+    fn ignores(&self, dependency_name: &str) -> bool {
+        self.ignore
+            .iter()
+            .any(|pattern| matches_glob(pattern, dependency_name))
+    }
+}
+
+fn strip_range_prefix(version: &str) -> &str {
+    [">=", "^", "~"]
+        .iter()
+        .find_map(|prefix| version.strip_prefix(prefix))
+        .map_or(version, str::trim_start)
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any sequence of characters.
+fn matches_glob(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = name.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+fn is_bundled(
+    package_json: &PackageJson,
+    section: DependencySection,
+    dependency_name: &str,
+) -> bool {
+    match &package_json.bundle_dependencies {
+        Some(BundleDependencies::Names(names)) => names.iter().any(|name| name == dependency_name),
+        Some(BundleDependencies::All(all)) => *all && section == DependencySection::Dependencies,
+        None => false,
+    }
+}
+
+fn override_version(package_json: &PackageJson, dependency_name: &str) -> Option<String> {
+    let override_value = package_json.overrides.as_ref()?.get(dependency_name)?;
+    let version = match override_value {
+        Value::String(version) => version,
+        Value::Object(nested_overrides) => nested_overrides.get(".")?.as_str()?,
+        _ => return None,
+    };
+
+    Version::parse(version).ok().map(|_| version.to_string())
+}
+
+/// Exact version yarn `resolutions` force `dependency_name` to, through its own name or a
+/// `**/` pattern.
+fn resolution_version(package_json: &PackageJson, dependency_name: &str) -> Option<String> {
+    let resolutions = package_json.resolutions.as_ref()?.as_object()?;
+    let version = resolutions
+        .get(dependency_name)
+        .or_else(|| resolutions.get(&format!("**/{dependency_name}")))?
+        .as_str()?;
+
+    Version::parse(version).ok().map(|_| version.to_string())
+}
+
+/// How a declared dependency is handled, with the reason it is skipped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind", content = "reason")]
+pub enum PinDecision {
+    Pin,
+    AlreadyPinned,
+    Unresolved,
+    Skip(String),
+}
+
+impl fmt::Display for PinDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PinDecision::Pin => f.write_str("pin"),
+            PinDecision::AlreadyPinned => f.write_str("already pinned"),
+            PinDecision::Unresolved => f.write_str("unresolved in the lock file"),
+            PinDecision::Skip(reason) => write!(f, "skip, {reason}"),
+        }
+    }
+}
+
+/// Trace of a single dependency declaration, see [`explain_dependency`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyExplanation {
+    pub section: DependencySection,
+    pub specifier: DependencySpecifier,
+    pub resolver_key: String,
+    pub locked_version: Option<String>,
+    pub decision: PinDecision,
+}
+
+fn explain_declaration(
+    package_json: &PackageJson,
+    resolver: &DependencyVersionResolver,
+    options: &PinOptions,
+    section: DependencySection,
+    dependency_name: &str,
+    specifier: &DependencySpecifier,
+) -> DependencyExplanation {
+    let version = specifier.as_str().unwrap_or("*");
+    let overridden_version = options
+        .respect_overrides
+        .then(|| override_version(package_json, dependency_name))
+        .flatten()
+        .or_else(|| {
+            options
+                .apply_resolutions
+                .then(|| resolution_version(package_json, dependency_name))
+                .flatten()
+        });
+    if let Some(overridden_version) = &overridden_version {
+        debug!(
+            "Dependency {} is overridden to {}.",
+            dependency_name, overridden_version
+        );
+    }
+    let locked_version = overridden_version.or_else(|| {
+        resolver
+            .resolve(dependency_name, version)
+            .map(|locked_dependency| locked_dependency.version.clone())
+    });
+    let decision = pin_decision(
+        package_json,
+        options,
+        section,
+        dependency_name,
+        specifier.as_str(),
+        locked_version.as_deref(),
+    );
+
+    DependencyExplanation {
+        section,
+        specifier: specifier.clone(),
+        resolver_key: (resolver.resolve_dependency_key)(dependency_name, version),
+        locked_version,
+        decision,
+    }
+}
+
+fn pin_decision(
+    package_json: &PackageJson,
+    options: &PinOptions,
+    section: DependencySection,
+    dependency_name: &str,
+    version: Option<&str>,
+    locked_version: Option<&str>,
+) -> PinDecision {
+    let skip = |reason: &str| PinDecision::Skip(reason.to_string());
+    let Some(version) = version else {
+        return skip("it is not declared with a version string");
+    };
+    if dependency_name.starts_with("file") {
+        return skip("it is using a local path as version");
+    }
+    if version.starts_with("workspace:") {
+        return skip("it references a workspace");
+    }
+    if !options.includes(section) {
+        return PinDecision::Skip(format!("the {section} section is skipped"));
+    }
+    if !options.includes_dependency(section, dependency_name) {
+        return skip("it is outside the dev scopes");
+    }
+    if options.ignores(dependency_name) {
+        return skip("it matches an ignore pattern");
+    }
+    if options
+        .only_dependencies
+        .as_ref()
+        .is_some_and(|only_dependencies| !only_dependencies.contains(dependency_name))
+    {
+        return skip("it is not among the dependencies to pin");
+    }
+    if is_bundled(package_json, section, dependency_name) {
+        return skip("it is bundled");
+    }
+    let Some(locked_version) = locked_version else {
+        return PinDecision::Unresolved;
+    };
+    if options.strip_prefix && strip_range_prefix(version) == locked_version {
+        return PinDecision::Skip(format!("range {version} starts at its locked version"));
+    }
+
+    if Version::parse(version).is_err() && version != options.pin_style.specifier(locked_version) {
+        PinDecision::Pin
+    } else {
+        PinDecision::AlreadyPinned
+    }
+}
+
+/// How every declaration of `dependency_name` is handled, one entry per section declaring it.
+pub fn explain_dependency(
+    package_json: &PackageJson,
+    resolver: &DependencyVersionResolver,
+    options: &PinOptions,
+    dependency_name: &str,
+) -> Vec<DependencyExplanation> {
+    package_json
+        .dependency_sections()
+        .into_iter()
+        .filter_map(|(section, dependencies)| {
+            let specifier = dependencies?.get(dependency_name)?;
+            Some(explain_declaration(
+                package_json,
+                resolver,
+                options,
+                section,
+                dependency_name,
+                specifier,
+            ))
+        })
+        .collect()
+}
+
+/// Whether `version` points at a local path, a git repository or a workspace rather than a
+/// registry, which lock files do not always list by name.
+fn is_non_registry_specifier(version: &str) -> bool {
+    [
+        "file:",
+        "link:",
+        "portal:",
+        "workspace:",
+        "git:",
+        "git+",
+        "github:",
+    ]
+    .iter()
+    .any(|prefix| version.starts_with(prefix))
+        || version.contains("://")
+}
+
+/// Names of the declared dependencies missing from the lock file, sorted, leaving out local,
+/// git and workspace specifiers. A sign of a lock file out of date with package.json.
+pub fn unresolved_dependencies(
+    package_json: &PackageJson,
+    resolver: &DependencyVersionResolver,
+    options: &PinOptions,
+) -> BTreeSet<String> {
+    package_json
+        .dependency_sections()
+        .into_iter()
+        .flat_map(|(section, dependencies)| {
+            dependencies
+                .into_iter()
+                .flatten()
+                .map(move |(name, specifier)| (section, name, specifier))
+        })
+        .filter(|(_, _, specifier)| !specifier.as_str().is_some_and(is_non_registry_specifier))
+        .filter(|(section, name, specifier)| {
+            explain_declaration(package_json, resolver, options, *section, name, specifier).decision
+                == PinDecision::Unresolved
+        })
+        .map(|(_, name, _)| name.clone())
+        .collect()
+}
+
+/// A dependency declared in several sections yields one entry per section, so every
+/// occurrence gets pinned.
+#[tracing::instrument(skip_all)]
+pub fn compute_versions_to_pin(
+    package_json: &PackageJson,
+    resolver: &DependencyVersionResolver,
+    options: &PinOptions,
+) -> Result<Vec<VersionToPin>, Error> {
+    let mut result = Vec::new();
+
+    for (section, dependencies) in package_json.dependency_sections() {
+        if !options.includes(section) {
+            debug!("Skipping {} section.", section);
+            continue;
+        }
+        let Some(dependencies) = dependencies else {
+            continue;
+        };
+        let mut dependencies: Vec<_> = dependencies.iter().collect();
+        dependencies.sort_by_key(|(name, _)| *name);
+
+        for (dependency_name, specifier) in dependencies {
+            let explanation = explain_declaration(
+                package_json,
+                resolver,
+                options,
+                section,
+                dependency_name,
+                specifier,
+            );
+            match (explanation.decision, explanation.locked_version) {
+                (PinDecision::Pin, Some(locked_version)) => {
+                    let version = specifier.as_str().unwrap_or_default();
+                    debug!(
+                        "Dependency {} version is not pinned: {} -> {}.",
+                        dependency_name, version, locked_version
+                    );
+
+                    result.push(VersionToPin {
+                        section,
+                        dependency: dependency_name.clone(),
+                        package_version: version.to_string(),
+                        locked_version,
+                    });
+                }
+                (decision, _) => debug!("Dependency {}: {}.", dependency_name, decision),
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PinPlan {
+    pub versions_to_pin: Vec<VersionToPin>,
+    /// Pins left out because they would downgrade the declared range, see
+    /// [`PinOptions::allow_downgrade`].
+    pub skipped_downgrades: Vec<VersionToPin>,
+    pub pin_style: PinStyle,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinStats {
+    pub total: usize,
+    pub per_section: BTreeMap<DependencySection, usize>,
+}
+
+impl PinPlan {
+    pub fn is_empty(&self) -> bool {
+        self.versions_to_pin.is_empty()
+    }
+
+    pub fn stats(&self) -> PinStats {
+        let mut stats = PinStats::default();
+        for version_to_pin in &self.versions_to_pin {
+            stats.total += 1;
+            *stats.per_section.entry(version_to_pin.section).or_default() += 1;
+        }
+
+        stats
+    }
+}
+
+pub fn compute_pin_plan(
+    package_json: &PackageJson,
+    resolver: &DependencyVersionResolver,
+    options: &PinOptions,
+) -> Result<PinPlan, Error> {
+    let versions_to_pin = compute_versions_to_pin(package_json, resolver, options)?;
+    if options.allow_downgrade {
+        return Ok(PinPlan {
+            versions_to_pin,
+            skipped_downgrades: Vec::new(),
+            pin_style: options.pin_style,
+        });
+    }
+
+    let (skipped_downgrades, versions_to_pin) = versions_to_pin
+        .into_iter()
+        .partition(VersionToPin::is_downgrade);
+    Ok(PinPlan {
+        versions_to_pin,
+        skipped_downgrades,
+        pin_style: options.pin_style,
+    })
+}
+
+/// Plans the dependency versions to pin from an in-memory manifest and lock file.
+///
+/// ```
+/// use riri_node_tools::pin::plan_pins;
+/// use riri_node_tools::types::{NpmLock, PackageManagerLock};
+///
+/// let package_json = serde_json::from_str(
+///     r#"{ "name": "app", "dependencies": { "lodash": "^4.17.0" } }"#,
+/// )?;
+/// let lock = PackageManagerLock::Npm(NpmLock::Version3(serde_json::from_str(
+///     r#"{ "lockfileVersion": 3, "packages": { "node_modules/lodash": { "version": "4.17.21" } } }"#,
+/// )?));
+///
+/// let pin_plan = plan_pins(&package_json, &lock)?;
+///
+/// assert_eq!(pin_plan.versions_to_pin[0].dependency, "lodash");
+/// assert_eq!(pin_plan.versions_to_pin[0].package_version, "^4.17.0");
+/// assert_eq!(pin_plan.versions_to_pin[0].locked_version, "4.17.21");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn plan_pins(package_json: &PackageJson, lock: &PackageManagerLock) -> Result<PinPlan, Error> {
+    compute_pin_plan(package_json, &build_resolver(lock), &PinOptions::default())
+}
+
+/// Writes the locked versions of `pin_plan`, in its pin style, into a raw package.json, leaving
+/// every other field untouched.
+pub fn apply_pins(raw_package: &mut Value, pin_plan: &PinPlan) {
+    for version_to_pin in &pin_plan.versions_to_pin {
+        if let Some(locked_version) = raw_package
+            .get_mut(version_to_pin.section.to_string())
+            .and_then(|dependencies| dependencies.get_mut(&version_to_pin.dependency))
+        {
+            *locked_version =
+                Value::String(pin_plan.pin_style.specifier(&version_to_pin.locked_version));
+        }
+    }
+}
+
+/// Keeps every dependency only in the first section declaring it, `dependencies` coming before
+/// `devDependencies` and `optionalDependencies`, in a raw package.json. Sections left empty are
+/// removed. Returns the removed declarations.
+pub fn collapse_duplicate_sections(raw_package: &mut Value) -> Vec<(String, DependencySection)> {
+    let mut declared = BTreeSet::new();
+    let mut removed = Vec::new();
+
+    for section in [
+        DependencySection::Dependencies,
+        DependencySection::DevDependencies,
+        DependencySection::OptionalDependencies,
+    ] {
+        let Some(Value::Object(dependencies)) = raw_package.get_mut(section.to_string()) else {
+            continue;
+        };
+        let duplicates: Vec<String> = dependencies
+            .keys()
+            .filter(|name| !declared.insert((*name).clone()))
+            .cloned()
+            .collect();
+        if duplicates.is_empty() {
+            continue;
+        }
+
+        for name in duplicates {
+            dependencies.shift_remove(&name);
+            removed.push((name, section));
+        }
+        if dependencies.is_empty() {
+            if let Some(raw_package) = raw_package.as_object_mut() {
+                raw_package.shift_remove(&section.to_string());
+            }
+        }
+    }
+
+    removed
+}
+
+/// Pretty-prints a raw package.json with the given indentation and line ending, with a trailing
+/// newline. Minified files have no indentation to detect and get two spaces.
+///
+/// ```
+/// use detect_indent::detect_indent;
+/// use riri_node_tools::pin::{apply_pins, plan_pins, serialize_package_json};
+/// use riri_node_tools::types::{NpmLock, PackageManagerLock};
+///
+/// let contents = "{\n\t\"name\": \"app\",\n\t\"dependencies\": {\n\t\t\"lodash\": \"^4.17.0\"\n\t}\n}\n";
+/// let mut raw_package = serde_json::from_str(contents)?;
+/// let lock = PackageManagerLock::Npm(NpmLock::Version3(serde_json::from_str(
+///     r#"{ "lockfileVersion": 3, "packages": { "node_modules/lodash": { "version": "4.17.21" } } }"#,
+/// )?));
+///
+/// apply_pins(&mut raw_package, &plan_pins(&serde_json::from_str(contents)?, &lock)?);
+/// let bytes = serialize_package_json(&raw_package, &detect_indent(contents), "\r\n");
+///
+/// assert_eq!(
+///     String::from_utf8(bytes)?,
+///     "{\r\n\t\"name\": \"app\",\r\n\t\"dependencies\": {\r\n\t\t\"lodash\": \"4.17.21\"\r\n\t}\r\n}\r\n"
+/// );
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn serialize_package_json(raw_package: &Value, indent: &Indent, newline: &str) -> Vec<u8> {
+    let indent = match indent.indent() {
+        "" => "  ",
+        indent => indent,
+    };
+    let mut buf = Vec::new();
+    let mut serializer = serde_json::Serializer::with_formatter(
+        &mut buf,
+        PrettyFormatter::with_indent(indent.as_bytes()),
+    );
+    raw_package
+        .serialize(&mut serializer)
+        .expect("Failed to serialize JSON content");
+    buf.push(b'\n');
+
+    if newline == "\n" {
+        return buf;
+    }
+    // Line breaks inside JSON strings are escaped, so every raw newline is a line ending.
+    let mut converted = Vec::with_capacity(buf.len());
+    for byte in buf {
+        if byte == b'\n' {
+            converted.extend_from_slice(newline.as_bytes());
+        } else {
+            converted.push(byte);
+        }
+    }
+    converted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{parse_fixture_lock, parse_fixture_package};
+    use crate::types::PackageManager;
+
+    #[test]
+    fn pnpm_v6_pins_strip_peer_suffixes() {
+        let package_json = parse_fixture_package("pnpm-v6-peer-suffix");
+        let resolver = build_resolver(&parse_fixture_lock(
+            "pnpm-v6-peer-suffix",
+            PackageManager::Pnpm,
+        ));
+
+        let versions_to_pin =
+            compute_versions_to_pin(&package_json, &resolver, &PinOptions::default())
+                .expect("Unable to compute dependency versions to pin");
+        let locked_versions: Vec<(&str, &str)> = versions_to_pin
+            .iter()
+            .map(|version_to_pin| {
+                (
+                    version_to_pin.dependency.as_str(),
+                    version_to_pin.locked_version.as_str(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            locked_versions,
+            vec![("react", "18.2.0"), ("react-dom", "18.2.0")]
+        );
+    }
+
+    #[test]
+    fn explain_dependency_traces_found_and_missing_dependencies() {
+        let package_json = parse_fixture_package("npm-unresolved-dependency");
+        let resolver = build_resolver(&parse_fixture_lock(
+            "npm-unresolved-dependency",
+            PackageManager::Npm,
+        ));
+        let explain =
+            |name| explain_dependency(&package_json, &resolver, &PinOptions::default(), name);
+
+        assert_eq!(
+            explain("lodash"),
+            vec![DependencyExplanation {
+                section: DependencySection::Dependencies,
+                specifier: DependencySpecifier::Version("^4.17.0".to_string()),
+                resolver_key: "node_modules/lodash".to_string(),
+                locked_version: Some("4.17.21".to_string()),
+                decision: PinDecision::Pin,
+            }]
+        );
+        assert_eq!(
+            explain("left-pad"),
+            vec![DependencyExplanation {
+                section: DependencySection::Dependencies,
+                specifier: DependencySpecifier::Version("^1.3.0".to_string()),
+                resolver_key: "node_modules/left-pad".to_string(),
+                locked_version: None,
+                decision: PinDecision::Unresolved,
+            }]
+        );
+        assert!(explain("chalk").is_empty());
+    }
+
+    #[test]
+    fn respect_overrides_uses_override_target() {
+        let package_json = parse_fixture_package("npm-overrides");
+        let resolver = build_resolver(&parse_fixture_lock("npm-overrides", PackageManager::Npm));
+        let locked_versions = |options: &PinOptions| -> Vec<String> {
+            compute_versions_to_pin(&package_json, &resolver, options)
+                .expect("Unable to compute dependency versions to pin")
+                .into_iter()
+                .map(|version_to_pin| version_to_pin.locked_version)
+                .collect()
+        };
+
+        assert_eq!(locked_versions(&PinOptions::default()), vec!["4.17.21"]);
+        assert_eq!(
+            locked_versions(&PinOptions {
+                respect_overrides: true,
+                ..PinOptions::default()
+            }),
+            vec!["4.17.20"]
+        );
+    }
+
+    #[test]
+    fn apply_resolutions_uses_resolution_version() {
+        let package_json = parse_fixture_package("yarn-resolutions");
+        let resolver = build_resolver(&parse_fixture_lock(
+            "yarn-resolutions",
+            PackageManager::Yarn,
+        ));
+        let locked_versions = |options: &PinOptions| -> Vec<(String, String)> {
+            compute_versions_to_pin(&package_json, &resolver, options)
+                .expect("Unable to compute dependency versions to pin")
+                .into_iter()
+                .map(|version_to_pin| (version_to_pin.dependency, version_to_pin.locked_version))
+                .collect()
+        };
+
+        assert_eq!(
+            locked_versions(&PinOptions::default()),
+            vec![
+                ("lodash".to_string(), "4.17.21".to_string()),
+                ("@types/node".to_string(), "20.10.5".to_string())
+            ]
+        );
+        assert_eq!(
+            locked_versions(&PinOptions {
+                apply_resolutions: true,
+                ..PinOptions::default()
+            }),
+            vec![
+                ("lodash".to_string(), "4.17.20".to_string()),
+                ("@types/node".to_string(), "20.10.5".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn collapse_duplicate_sections_keeps_the_first_section() {
+        let mut raw_package = serde_json::json!({
+            "name": "app",
+            "optionalDependencies": { "fsevents": "^2.3.0", "lodash": "^4.17.0" },
+            "devDependencies": { "lodash": "^4.17.0", "prettier": "^3.0.0" },
+            "dependencies": { "lodash": "4.17.21" },
+        });
+
+        assert_eq!(
+            collapse_duplicate_sections(&mut raw_package),
+            vec![
+                ("lodash".to_string(), DependencySection::DevDependencies),
+                (
+                    "lodash".to_string(),
+                    DependencySection::OptionalDependencies
+                ),
+            ]
+        );
+        assert_eq!(
+            raw_package,
+            serde_json::json!({
+                "name": "app",
+                "optionalDependencies": { "fsevents": "^2.3.0" },
+                "devDependencies": { "prettier": "^3.0.0" },
+                "dependencies": { "lodash": "4.17.21" },
+            })
+        );
+        assert!(collapse_duplicate_sections(&mut raw_package).is_empty());
+    }
+
+    fn pinned_sections(options: &PinOptions) -> Vec<(DependencySection, String)> {
+        let package_json = parse_fixture_package("npm-sections");
+        let resolver = build_resolver(&parse_fixture_lock("npm-sections", PackageManager::Npm));
+
+        compute_versions_to_pin(&package_json, &resolver, options)
+            .expect("Unable to compute dependency versions to pin")
+            .into_iter()
+            .map(|version_to_pin| (version_to_pin.section, version_to_pin.dependency))
+            .collect()
+    }
+
+    #[test]
+    fn prod_only_pins_dependencies() {
+        assert_eq!(pinned_sections(&PinOptions::default()).len(), 4);
+        assert_eq!(
+            pinned_sections(&PinOptions {
+                prod: true,
+                ..PinOptions::default()
+            }),
+            vec![(DependencySection::Dependencies, "lodash".to_string())]
+        );
+    }
+
+    #[test]
+    fn object_dependencies_are_skipped() {
+        let package_json = parse_fixture_package("npm-object-dependency");
+        let resolver = build_resolver(&parse_fixture_lock(
+            "npm-object-dependency",
+            PackageManager::Npm,
+        ));
+
+        let pinned: Vec<String> =
+            compute_versions_to_pin(&package_json, &resolver, &PinOptions::default())
+                .expect("Unable to compute dependency versions to pin")
+                .into_iter()
+                .map(|version_to_pin| version_to_pin.dependency)
+                .collect();
+
+        assert_eq!(pinned, vec!["lodash"]);
+    }
+
+    #[test]
+    fn duplicate_dependency_is_pinned_in_every_section() {
+        let package_json = parse_fixture_package("npm-duplicate-sections");
+        let resolver = build_resolver(&parse_fixture_lock(
+            "npm-duplicate-sections",
+            PackageManager::Npm,
+        ));
+
+        let versions_to_pin: Vec<(DependencySection, String, String)> =
+            compute_versions_to_pin(&package_json, &resolver, &PinOptions::default())
+                .expect("Unable to compute dependency versions to pin")
+                .into_iter()
+                .map(|version_to_pin| {
+                    (
+                        version_to_pin.section,
+                        version_to_pin.dependency,
+                        version_to_pin.locked_version,
+                    )
+                })
+                .collect();
+
+        assert_eq!(
+            versions_to_pin,
+            vec![
+                (
+                    DependencySection::Dependencies,
+                    "lodash".to_string(),
+                    "4.17.21".to_string()
+                ),
+                (
+                    DependencySection::Dependencies,
+                    "react".to_string(),
+                    "18.2.0".to_string()
+                ),
+                (
+                    DependencySection::DevDependencies,
+                    "lodash".to_string(),
+                    "4.17.21".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignore_optional_skips_optional_dependencies() {
+        assert_eq!(
+            pinned_sections(&PinOptions {
+                ignore_optional: true,
+                ..PinOptions::default()
+            }),
+            vec![
+                (DependencySection::Dependencies, "lodash".to_string()),
+                (DependencySection::DevDependencies, "prettier".to_string()),
+                (DependencySection::DevDependencies, "typescript".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn downgrades_are_skipped_unless_allowed() {
+        let package_json = parse_fixture_package("npm-downgrade");
+        let resolver = build_resolver(&parse_fixture_lock("npm-downgrade", PackageManager::Npm));
+        let pinned = |options: &PinOptions| -> (Vec<String>, Vec<String>) {
+            let pin_plan = compute_pin_plan(&package_json, &resolver, options)
+                .expect("Unable to compute dependency versions to pin");
+            let names = |versions_to_pin: Vec<VersionToPin>| {
+                versions_to_pin
+                    .into_iter()
+                    .map(|version_to_pin| version_to_pin.dependency)
+                    .collect()
+            };
+            (
+                names(pin_plan.versions_to_pin),
+                names(pin_plan.skipped_downgrades),
+            )
+        };
+
+        assert_eq!(
+            pinned(&PinOptions::default()),
+            (vec!["lodash".to_string()], vec!["legacy-lib".to_string()])
+        );
+        assert_eq!(
+            pinned(&PinOptions {
+                allow_downgrade: true,
+                ..PinOptions::default()
+            }),
+            (
+                vec!["legacy-lib".to_string(), "lodash".to_string()],
+                Vec::new()
+            )
+        );
+    }
+
+    #[test]
+    fn dev_scopes_filter_dev_dependencies() {
+        let package_json = parse_fixture_package("npm-dev-scopes");
+        let resolver = build_resolver(&parse_fixture_lock("npm-dev-scopes", PackageManager::Npm));
+
+        let pinned: Vec<String> = compute_versions_to_pin(
+            &package_json,
+            &resolver,
+            &PinOptions {
+                dev_scopes: vec!["@types/*".to_string()],
+                ..PinOptions::default()
+            },
+        )
+        .expect("Unable to compute dependency versions to pin")
+        .into_iter()
+        .map(|version_to_pin| version_to_pin.dependency)
+        .collect();
+
+        assert_eq!(pinned, vec!["lodash", "@types/lodash", "@types/node"]);
+    }
+
+    #[test]
+    fn unresolved_dependencies_leave_out_non_registry_specifiers() {
+        let package_json = parse_fixture_package("npm-stale-lock");
+        let resolver = build_resolver(&parse_fixture_lock("npm-stale-lock", PackageManager::Npm));
+
+        assert_eq!(
+            unresolved_dependencies(&package_json, &resolver, &PinOptions::default()),
+            BTreeSet::from(["chalk".to_string(), "left-pad".to_string()])
+        );
+        assert_eq!(
+            unresolved_dependencies(
+                &package_json,
+                &resolver,
+                &PinOptions {
+                    prod: true,
+                    ..PinOptions::default()
+                }
+            ),
+            BTreeSet::from(["left-pad".to_string()])
+        );
+    }
+
+    #[test]
+    fn ignore_patterns_skip_dependencies_in_every_section() {
+        let package_json = parse_fixture_package("npm-dev-scopes");
+        let resolver = build_resolver(&parse_fixture_lock("npm-dev-scopes", PackageManager::Npm));
+
+        let pinned: Vec<String> = compute_versions_to_pin(
+            &package_json,
+            &resolver,
+            &PinOptions {
+                ignore: vec!["lodash".to_string(), "@types/*".to_string()],
+                ..PinOptions::default()
+            },
+        )
+        .expect("Unable to compute dependency versions to pin")
+        .into_iter()
+        .map(|version_to_pin| version_to_pin.dependency)
+        .collect();
+
+        assert_eq!(pinned, vec!["typescript"]);
+    }
+
+    #[test]
+    fn strip_prefix_skips_ranges_starting_at_the_locked_version() {
+        let lock = PackageManagerLock::Npm(crate::types::NpmLock::Version3(
+            serde_json::from_str(
+                r#"{ "lockfileVersion": 3, "packages": { "node_modules/lodash": { "version": "4.17.21" } } }"#,
+            )
+            .expect("Invalid lock"),
+        ));
+        let resolver = build_resolver(&lock);
+        let pinned = |range: &str, strip_prefix: bool| {
+            let package_json = serde_json::from_value(serde_json::json!({
+                "name": "app",
+                "dependencies": { "lodash": range }
+            }))
+            .expect("Invalid package.json");
+            !compute_versions_to_pin(
+                &package_json,
+                &resolver,
+                &PinOptions {
+                    strip_prefix,
+                    ..PinOptions::default()
+                },
+            )
+            .expect("Unable to compute dependency versions to pin")
+            .is_empty()
+        };
+
+        for range in ["^4.17.21", "~4.17.21", ">=4.17.21", ">= 4.17.21"] {
+            assert!(pinned(range, false), "{range}");
+            assert!(!pinned(range, true), "{range}");
+        }
+        assert!(pinned("^4.17.0", true));
+    }
+
+    #[test]
+    fn apply_pins_in_each_pin_style() {
+        let lock = PackageManagerLock::Npm(crate::types::NpmLock::Version3(
+            serde_json::from_str(
+                r#"{ "lockfileVersion": 3, "packages": { "node_modules/lodash": { "version": "4.17.21" } } }"#,
+            )
+            .expect("Invalid lock"),
+        ));
+        let resolver = build_resolver(&lock);
+        let written = |range: &str, pin_style: PinStyle| {
+            let mut raw_package = serde_json::json!({
+                "name": "app",
+                "dependencies": { "lodash": range }
+            });
+            let pin_plan = compute_pin_plan(
+                &serde_json::from_value(raw_package.clone()).expect("Invalid package.json"),
+                &resolver,
+                &PinOptions {
+                    pin_style,
+                    ..PinOptions::default()
+                },
+            )
+            .expect("Unable to compute dependency versions to pin");
+            apply_pins(&mut raw_package, &pin_plan);
+            raw_package["dependencies"]["lodash"].clone()
+        };
+
+        assert_eq!(written("^4.17.0", PinStyle::Exact), "4.17.21");
+        assert_eq!(written("^4.17.0", PinStyle::Tilde), "~4.17.21");
+        assert_eq!(written("^4.17.0", PinStyle::Caret), "^4.17.21");
+        assert_eq!(written("^4.17.21", PinStyle::Tilde), "~4.17.21");
+        assert_eq!(written("~4.17.21", PinStyle::Tilde), "~4.17.21");
+        assert_eq!(written("4.17.20", PinStyle::Caret), "4.17.20");
+    }
+
+    #[test]
+    fn glob_matching() {
+        assert!(matches_glob("@types/*", "@types/node"));
+        assert!(!matches_glob("@types/*", "typescript"));
+        assert!(matches_glob("eslint-*-react", "eslint-plugin-react"));
+        assert!(matches_glob("typescript", "typescript"));
+        assert!(!matches_glob("typescript", "typescript-eslint"));
+        assert!(matches_glob("*", "prettier"));
+    }
+
+    #[test]
+    fn pin_plan_stats_count_per_section() {
+        let package_json = parse_fixture_package("npm-sections");
+        let lock = parse_fixture_lock("npm-sections", PackageManager::Npm);
+
+        let stats = plan_pins(&package_json, &lock)
+            .expect("Unable to compute dependency versions to pin")
+            .stats();
+
+        assert_eq!(stats.total, 4);
+        assert_eq!(
+            stats.per_section.into_iter().collect::<Vec<_>>(),
+            vec![
+                (DependencySection::Dependencies, 1),
+                (DependencySection::DevDependencies, 2),
+                (DependencySection::OptionalDependencies, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn bundled_dependencies_are_not_pinned() {
+        let package_json = parse_fixture_package("npm-bundled");
+        let lock = parse_fixture_lock("npm-bundled", PackageManager::Npm);
+
+        let dependencies: Vec<String> = plan_pins(&package_json, &lock)
+            .expect("Unable to compute dependency versions to pin")
+            .versions_to_pin
+            .into_iter()
+            .map(|version_to_pin| version_to_pin.dependency)
+            .collect();
+
+        assert_eq!(dependencies, vec!["lodash"]);
+    }
+
+    #[test]
+    fn compute_nothing_to_pin_for_empty_dependencies() {
+        let package_json = parse_fixture_package("empty-dependencies");
+        let resolver = build_resolver(&parse_fixture_lock(
+            "empty-dependencies",
+            PackageManager::Pnpm,
+        ));
+        let versions_to_pin =
+            compute_versions_to_pin(&package_json, &resolver, &PinOptions::default())
+                .expect("Unable to compute dependency versions to pin");
+
+        assert!(resolver.locked_dependencies.is_empty());
+        assert!(versions_to_pin.is_empty());
+    }
+}
@@ -0,0 +1,117 @@
+use crate::types::Npmrc;
+
+pub const DEFAULT_REGISTRY: &str = "https://registry.npmjs.org/";
+
+fn package_scope(package: &str) -> Option<&str> {
+    if package.starts_with('@') {
+        package.split_once('/').map(|(scope, _)| scope)
+    } else {
+        None
+    }
+}
+
+pub fn registry_for<'a>(npmrc: &'a Npmrc, package: &str) -> &'a str {
+    package_scope(package)
+        .and_then(|scope| npmrc.scoped_registries.get(scope))
+        .or(npmrc.registry.as_ref())
+        .map_or(DEFAULT_REGISTRY, String::as_str)
+}
+
+/// `registry` ending with a `/`, so that `//npm.acme.example` never matches
+/// `//npm.acme.example.evil.com/`.
+fn with_trailing_slash(registry: &str) -> String {
+    format!("{}/", registry.trim_end_matches('/'))
+}
+
+pub fn auth_token_for<'a>(npmrc: &'a Npmrc, registry: &str) -> Option<&'a str> {
+    let nerf_dart = with_trailing_slash(
+        registry
+            .trim_start_matches("https:")
+            .trim_start_matches("http:"),
+    );
+
+    npmrc
+        .auth_tokens
+        .iter()
+        .filter(|(registry_prefix, _)| nerf_dart.starts_with(&with_trailing_slash(registry_prefix)))
+        .max_by_key(|(registry_prefix, _)| registry_prefix.len())
+        .map(|(_, token)| token.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_npmrc;
+    use std::path::PathBuf;
+
+    fn fixture_npmrc() -> Npmrc {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/npmrc/.npmrc");
+        parse_npmrc(&path).expect("Unable to parse .npmrc fixture")
+    }
+
+    #[test]
+    fn select_registry_per_package() {
+        let npmrc = fixture_npmrc();
+
+        assert_eq!(
+            registry_for(&npmrc, "@acme/ui"),
+            "https://npm.acme.example/"
+        );
+        assert_eq!(
+            registry_for(&npmrc, "lodash"),
+            "https://registry.example.com/"
+        );
+        assert_eq!(
+            registry_for(&Npmrc::default(), "@acme/ui"),
+            DEFAULT_REGISTRY
+        );
+    }
+
+    #[test]
+    fn select_auth_token_per_registry() {
+        let npmrc = fixture_npmrc();
+
+        assert_eq!(
+            auth_token_for(&npmrc, "https://npm.acme.example/"),
+            Some("acme-secret-token")
+        );
+        assert_eq!(
+            auth_token_for(&npmrc, "https://registry.example.com/"),
+            None
+        );
+    }
+
+    #[test]
+    fn auth_token_is_not_sent_to_hosts_sharing_a_prefix() {
+        let mut npmrc = fixture_npmrc();
+        npmrc.auth_tokens.insert(
+            "//npm.other.example".to_string(),
+            "other-secret-token".to_string(),
+        );
+
+        assert_eq!(
+            auth_token_for(&npmrc, "https://npm.acme.example.evil.com/"),
+            None
+        );
+        assert_eq!(
+            auth_token_for(&npmrc, "https://npm.other.example.evil.com/"),
+            None
+        );
+        assert_eq!(
+            auth_token_for(&npmrc, "https://npm.other.example"),
+            Some("other-secret-token")
+        );
+        assert_eq!(
+            auth_token_for(&npmrc, "https://npm.acme.example/packages/"),
+            Some("acme-secret-token")
+        );
+    }
+
+    #[test]
+    fn redact_auth_tokens_in_debug_output() {
+        let debug_output = format!("{:?}", fixture_npmrc());
+
+        assert!(!debug_output.contains("acme-secret-token"));
+        assert!(debug_output.contains("***"));
+    }
+}
@@ -0,0 +1,879 @@
+use crate::types::{
+    flatten_npm_dependencies_tree, BunLock, Engine, LockDependency, LockFileResult,
+    LockfileVersion, NpmDependencies, NpmLock, NpmLockEngines, ObjectEngines, PackageManagerLock,
+    PnpmImporterV5, PnpmImporterV6, PnpmLock, VersionedDependencyOrResolved, YarnLockV2,
+};
+use crate::{engines, parser};
+use semver::Version;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tracing::{debug, warn};
+
+pub type ResolveDependencyKey = fn(name: &str, version: &str) -> String;
+pub type LockDependencies = HashMap<String, LockDependency>;
+
+#[derive(Debug)]
+pub struct DependencyVersionResolver {
+    pub locked_dependencies: LockDependencies,
+    pub resolve_dependency_key: ResolveDependencyKey,
+    /// Version of the lock file the resolver was built from, see [`build_resolver`].
+    pub lockfile_version: Option<LockfileVersion>,
+}
+
+impl DependencyVersionResolver {
+    /// Locked version of every entry, keyed as the lock file keys them, sorted for display.
+    pub fn locked_versions(&self) -> BTreeMap<&str, &str> {
+        self.locked_dependencies
+            .iter()
+            .map(|(key, dependency)| (key.as_str(), dependency.version.as_str()))
+            .collect()
+    }
+
+    /// Locked entry of `name`. npm lock files may only install a package below a workspace or
+    /// another package, as `packages/app/node_modules/{name}`, so the shallowest such entry is
+    /// used when the top level one is missing. A package yarn locked at several versions resolves
+    /// to the entry of the declared range, or else to the newest version that range allows.
+    pub fn resolve(&self, name: &str, version: &str) -> Option<&LockDependency> {
+        let key = (self.resolve_dependency_key)(name, version);
+        if let Some(dependency) = self.locked_dependencies.get(&key) {
+            return Some(dependency);
+        }
+        if key
+            .strip_prefix(name)
+            .is_some_and(|range| range.starts_with('@'))
+        {
+            return self
+                .locked_dependencies
+                .get(name)
+                .or_else(|| self.resolve_yarn_range(name, version));
+        }
+        if !key.starts_with("node_modules/") {
+            return None;
+        }
+
+        let suffix = format!("/{key}");
+        let (nested_key, dependency) = self
+            .locked_dependencies
+            .iter()
+            .filter(|(key, _)| key.ends_with(&suffix))
+            .min_by_key(|(key, _)| (key.matches("node_modules/").count(), key.len(), *key))?;
+        debug!("Dependency {} resolved using {}.", name, nested_key);
+
+        Some(dependency)
+    }
+
+    /// Newest version of `name` allowed by `range`, among the several versions yarn locked it at.
+    fn resolve_yarn_range(&self, name: &str, range: &str) -> Option<&LockDependency> {
+        let prefix = format!("{name}@");
+        let (key, dependency) = self
+            .locked_dependencies
+            .iter()
+            .filter(|(key, dependency)| {
+                key.starts_with(&prefix)
+                    && engines::version_satisfies(&dependency.version, range) == Some(true)
+            })
+            .max_by_key(|(_, dependency)| Version::parse(&dependency.version).ok())?;
+        debug!("Dependency {} resolved using {}.", name, key);
+
+        Some(dependency)
+    }
+}
+
+fn convert_array_to_object_engines(engines: Vec<String>) -> ObjectEngines {
+    let mut object_engines = ObjectEngines::new();
+
+    for engine_str in engines {
+        let lowercase_engine_str = engine_str.to_lowercase();
+
+        for engine_enum in [Engine::Node, Engine::Npm, Engine::Yarn] {
+            let engine_str_lowercase = format!("{engine_enum:?}").to_lowercase();
+            if lowercase_engine_str.contains(&engine_str_lowercase) {
+                let value = engine_str.trim_start_matches(&engine_str_lowercase).trim();
+
+                object_engines.insert(engine_enum, value.to_owned());
+                break;
+            }
+        }
+    }
+
+    object_engines
+}
+
+pub fn convert_npm_engines_to_object_engines(
+    engines: Option<NpmLockEngines>,
+) -> Option<ObjectEngines> {
+    match engines {
+        Some(NpmLockEngines::Object(object_engines)) => Some(object_engines),
+        Some(NpmLockEngines::Array(array_engines)) => {
+            Some(convert_array_to_object_engines(array_engines))
+        }
+        _ => None,
+    }
+}
+
+#[tracing::instrument]
+fn convert_npm_to_lock_dependencies(npm_dependencies: &NpmDependencies) -> LockDependencies {
+    let mut lock_dependencies = LockDependencies::new();
+
+    for (dependency_name, versioned_or_resolved) in npm_dependencies {
+        if dependency_name.is_empty() {
+            debug!("Skipping the root project entry.");
+            continue;
+        }
+
+        let lock_dependency = match versioned_or_resolved {
+            VersionedDependencyOrResolved::Versioned(versioned_dependency) => LockDependency {
+                version: versioned_dependency.version.clone(),
+                engines: convert_npm_engines_to_object_engines(
+                    versioned_dependency.engines.clone(),
+                ),
+            },
+            VersionedDependencyOrResolved::Resolved(resolved_dependency) => {
+                if let Some(resolved_key) = &resolved_dependency.resolved {
+                    debug!(
+                        "Dependency {} resolved using {}.",
+                        dependency_name, resolved_key
+                    );
+                    if let Some(resolved_dep) = npm_dependencies.get(resolved_key) {
+                        if let VersionedDependencyOrResolved::Versioned(versioned_dep) =
+                            resolved_dep
+                        {
+                            LockDependency {
+                                version: versioned_dep.version.clone(),
+                                engines: convert_npm_engines_to_object_engines(
+                                    versioned_dep.engines.clone(),
+                                ),
+                            }
+                        } else {
+                            debug!("Dependency {} version is undefined.", resolved_key);
+                            continue;
+                        }
+                    } else {
+                        debug!("Dependency {} is unresolved in dependencies.", resolved_key);
+                        continue;
+                    }
+                } else {
+                    continue;
+                }
+            }
+            VersionedDependencyOrResolved::Unversioned(_) => {
+                debug!("Dependency {} version is undefined.", dependency_name);
+                continue;
+            }
+        };
+
+        lock_dependencies.insert(dependency_name.clone(), lock_dependency);
+    }
+
+    lock_dependencies
+}
+
+#[tracing::instrument]
+fn npm_resolver(npm_lock: &NpmLock) -> DependencyVersionResolver {
+    let resolve_dependency: ResolveDependencyKey = |name, _| name.to_string();
+    let resolve_package: ResolveDependencyKey = |name, _| format!("node_modules/{name}");
+
+    match npm_lock {
+        NpmLock::Version1(lock) => DependencyVersionResolver {
+            locked_dependencies: convert_npm_to_lock_dependencies(&flatten_npm_dependencies_tree(
+                &lock.dependencies,
+            )),
+            resolve_dependency_key: resolve_dependency,
+            lockfile_version: None,
+        },
+        NpmLock::Version2(lock) => {
+            if let Some(packages) = &lock.packages {
+                DependencyVersionResolver {
+                    locked_dependencies: convert_npm_to_lock_dependencies(packages),
+                    resolve_dependency_key: resolve_package,
+                    lockfile_version: None,
+                }
+            } else {
+                DependencyVersionResolver {
+                    locked_dependencies: convert_npm_to_lock_dependencies(
+                        &flatten_npm_dependencies_tree(&lock.dependencies),
+                    ),
+                    resolve_dependency_key: resolve_dependency,
+                    lockfile_version: None,
+                }
+            }
+        }
+        NpmLock::Version3(lock) => DependencyVersionResolver {
+            locked_dependencies: convert_npm_to_lock_dependencies(&lock.packages),
+            resolve_dependency_key: resolve_package,
+            lockfile_version: None,
+        },
+    }
+}
+
+/// Name and range of a yarn Berry descriptor such as `lodash@npm:^4.17.0`, `None` for workspaces.
+fn yarn_descriptor(descriptor: &str) -> Option<(&str, &str)> {
+    let descriptor = descriptor.trim().trim_matches('"');
+    let (name, range) = descriptor.split_at(descriptor.get(1..)?.find('@')? + 1);
+    let range = &range[1..];
+
+    if range.starts_with("workspace:") {
+        return None;
+    }
+
+    Some((name, range))
+}
+
+pub(crate) fn yarn_descriptor_name(descriptor: &str) -> Option<&str> {
+    yarn_descriptor(descriptor).map(|(name, _)| name)
+}
+
+/// Key of a package locked at several versions, `lodash@^4.17.0` for `lodash@npm:^4.17.0`.
+fn yarn_dependency_key(name: &str, range: &str) -> String {
+    format!("{name}@{}", range.strip_prefix("npm:").unwrap_or(range))
+}
+
+/// Keys packages by name, or by descriptor when a package is locked at several versions, since
+/// the version to pin then depends on the range package.json declares.
+fn transform_yarn_v2_to_lock_dependencies(yarn_lock: &YarnLockV2) -> LockDependencies {
+    let mut locked_ranges: BTreeMap<&str, Vec<(&str, &String)>> = BTreeMap::new();
+
+    for (descriptors, dependency) in yarn_lock {
+        // Ranges matching a workspace, such as `app@npm:^1.0.0`, share its entry and its
+        // `0.0.0-use.local` placeholder version.
+        if dependency
+            .resolution
+            .as_deref()
+            .is_some_and(|resolution| resolution.contains("@workspace:"))
+        {
+            debug!("Skipping workspace entry {}.", descriptors);
+            continue;
+        }
+
+        for (name, range) in descriptors.split(',').filter_map(yarn_descriptor) {
+            locked_ranges
+                .entry(name)
+                .or_default()
+                .push((range, &dependency.version));
+        }
+    }
+
+    let mut lock_dependencies = LockDependencies::new();
+    for (name, ranges) in locked_ranges {
+        let lock_dependency = |version: &String| LockDependency {
+            version: version.clone(),
+            engines: None,
+        };
+        if ranges.iter().all(|(_, version)| *version == ranges[0].1) {
+            lock_dependencies.insert(name.to_string(), lock_dependency(ranges[0].1));
+            continue;
+        }
+
+        debug!("Dependency {} is locked at several versions.", name);
+        for (range, version) in ranges {
+            lock_dependencies.insert(yarn_dependency_key(name, range), lock_dependency(version));
+        }
+    }
+
+    lock_dependencies
+}
+
+fn yarn_resolver(yarn_lock_file: &YarnLockV2) -> DependencyVersionResolver {
+    DependencyVersionResolver {
+        locked_dependencies: transform_yarn_v2_to_lock_dependencies(yarn_lock_file),
+        resolve_dependency_key: yarn_dependency_key,
+        lockfile_version: None,
+    }
+}
+
+/// Resolved version of a pnpm v5 importer entry, without its `_peer@1.0.0` suffix. Some tooling
+/// swaps the `dependencies` and `specifiers` maps, so the exact version is taken from whichever
+/// holds one.
+fn resolve_pnpm_v5_version(version: &str, specifier: Option<&String>) -> String {
+    let version = version
+        .split_once('_')
+        .map_or(version, |(version, _)| version);
+    if Version::parse(version).is_err() {
+        if let Some(specifier) = specifier.filter(|specifier| Version::parse(specifier).is_ok()) {
+            return specifier.clone();
+        }
+    }
+
+    version.to_string()
+}
+
+/// Whether a pnpm importer version can be written into package.json. Hand edited lock files may
+/// leave it empty or malformed, local `link:` and `file:` versions are never pinned.
+fn is_pinnable_pnpm_version(name: &str, version: &str) -> bool {
+    if version.contains(':') {
+        debug!("Dependency {} is resolved to {}.", name, version);
+        return false;
+    }
+    if Version::parse(version).is_err() {
+        warn!(
+            "[WARN] Skipping {}: its pnpm lock file version {:?} is not a valid version.",
+            name, version
+        );
+        return false;
+    }
+
+    true
+}
+
+fn transform_pnpm_v5_to_lock_dependencies(
+    dependencies: Option<HashMap<String, String>>,
+    specifiers: Option<&HashMap<String, String>>,
+) -> LockDependencies {
+    dependencies.map_or_else(HashMap::new, |deps| {
+        deps.into_iter()
+            .filter_map(|(key, version)| {
+                let version = resolve_pnpm_v5_version(
+                    &version,
+                    specifiers.and_then(|specifiers| specifiers.get(&key)),
+                );
+                is_pinnable_pnpm_version(&key, &version).then_some((
+                    key,
+                    LockDependency {
+                        version,
+                        engines: None,
+                    },
+                ))
+            })
+            .collect()
+    })
+}
+
+pub(crate) fn strip_pnpm_peer_suffix(version: &str) -> &str {
+    version
+        .split_once('(')
+        .map_or(version, |(version, _)| version)
+}
+
+fn transform_pnpm_v6_to_lock_dependencies(
+    dependencies: Option<HashMap<String, LockDependency>>,
+) -> LockDependencies {
+    dependencies.map_or_else(HashMap::new, |deps| {
+        deps.into_iter()
+            .filter_map(|(key, dependency)| {
+                let version = strip_pnpm_peer_suffix(&dependency.version);
+                is_pinnable_pnpm_version(&key, version).then(|| {
+                    (
+                        key,
+                        LockDependency {
+                            version: version.to_string(),
+                            engines: dependency.engines,
+                        },
+                    )
+                })
+            })
+            .collect()
+    })
+}
+
+fn pnpm_resolver(pnpm_lock: &PnpmLock) -> DependencyVersionResolver {
+    let locked_dependencies: LockDependencies = match pnpm_lock {
+        PnpmLock::Version6(lock) => {
+            let importer = lock.importers.get(".").cloned().unwrap_or_else(|| {
+                debug!("Pnpm lock file has no resolved dependencies in current directory.");
+                PnpmImporterV6::default()
+            });
+            let dependencies = transform_pnpm_v6_to_lock_dependencies(importer.dependencies);
+            let dev_dependencies =
+                transform_pnpm_v6_to_lock_dependencies(importer.dev_dependencies);
+            let optional_dependencies =
+                transform_pnpm_v6_to_lock_dependencies(importer.optional_dependencies);
+
+            [dependencies, dev_dependencies, optional_dependencies]
+                .into_iter()
+                .flatten()
+                .collect()
+        }
+        PnpmLock::Version5(lock) => {
+            let importer = lock.importers.get(".").cloned().unwrap_or_else(|| {
+                debug!("Pnpm lock file has no resolved dependencies in current directory.");
+                PnpmImporterV5::default()
+            });
+            let specifiers = importer.specifiers.as_ref();
+            let dependencies =
+                transform_pnpm_v5_to_lock_dependencies(importer.dependencies, specifiers);
+            let dev_dependencies =
+                transform_pnpm_v5_to_lock_dependencies(importer.dev_dependencies, specifiers);
+            let optional_dependencies =
+                transform_pnpm_v5_to_lock_dependencies(importer.optional_dependencies, specifiers);
+
+            [dependencies, dev_dependencies, optional_dependencies]
+                .into_iter()
+                .flatten()
+                .collect()
+        }
+    };
+
+    DependencyVersionResolver {
+        locked_dependencies,
+        resolve_dependency_key: |name, _| name.to_string(),
+        lockfile_version: None,
+    }
+}
+
+/// Splits the `name@version` a Bun `packages` entry starts with.
+pub(crate) fn bun_package_entry(entry: &[Value]) -> Option<(&str, &str)> {
+    let descriptor = entry.first()?.as_str()?;
+    let separator = descriptor.get(1..)?.find('@')? + 1;
+    Some((&descriptor[..separator], &descriptor[separator + 1..]))
+}
+
+fn bun_resolver(bun_lock: &BunLock) -> DependencyVersionResolver {
+    // Nested entries are keyed `parent/name`, only the hoisted ones are keyed by their own name.
+    let locked_dependencies = bun_lock
+        .packages
+        .iter()
+        .filter_map(|(key, entry)| {
+            let (_, version) = bun_package_entry(entry).filter(|(name, _)| *name == key)?;
+            Some((
+                key.clone(),
+                LockDependency {
+                    version: version.to_string(),
+                    engines: None,
+                },
+            ))
+        })
+        .collect();
+
+    DependencyVersionResolver {
+        locked_dependencies,
+        resolve_dependency_key: |name, _| name.to_string(),
+        lockfile_version: None,
+    }
+}
+
+/// Indexes the locked version of every package in `lock`.
+///
+/// Infallible by design: malformed lock files are rejected by the [`parser`], and entries that
+/// cannot be resolved (workspace links, aliases without a version) are skipped rather than failing
+/// the whole lock.
+pub fn build_resolver(lock: &PackageManagerLock) -> DependencyVersionResolver {
+    let lockfile_version = lock.version();
+    debug!("Lock file uses {}.", lockfile_version);
+
+    let resolver = match lock {
+        PackageManagerLock::Npm(npm_lock) => npm_resolver(npm_lock),
+        PackageManagerLock::Yarn(yarn_lock) => yarn_resolver(yarn_lock),
+        PackageManagerLock::Pnpm(pnpm_lock) => pnpm_resolver(pnpm_lock),
+        PackageManagerLock::Bun(bun_lock) => bun_resolver(bun_lock),
+    };
+
+    DependencyVersionResolver {
+        lockfile_version: Some(lockfile_version),
+        ..resolver
+    }
+}
+
+/// Modification time and size of a lock file when its resolver was built.
+type LockStamp = Option<(SystemTime, u64)>;
+
+/// A parsed lock file and the resolver built from it.
+#[derive(Clone, Debug)]
+struct CachedLock {
+    stamp: LockStamp,
+    lock: Arc<PackageManagerLock>,
+    resolver: Arc<DependencyVersionResolver>,
+}
+
+fn lock_stamp(path: &Path) -> LockStamp {
+    let metadata = path.metadata().ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Parses each lock file at most once, however many packages resolve against it. A lock file
+/// whose modification time or size changed since is parsed again, so a long-lived cache stays
+/// up to date.
+#[derive(Debug, Default)]
+pub struct ResolverCache {
+    locks: HashMap<PathBuf, CachedLock>,
+    loads: usize,
+    allow_unknown_lock_version: bool,
+    max_lock_file_size: Option<u64>,
+}
+
+impl ResolverCache {
+    /// Reads lock files through [`parser::parse_lock_allowing_unknown_version`] when `allow` is set.
+    #[must_use]
+    pub fn allow_unknown_lock_version(mut self, allow: bool) -> Self {
+        self.allow_unknown_lock_version = allow;
+        self
+    }
+
+    /// Refuses lock files over `max_size` bytes, [`parser::DEFAULT_MAX_LOCK_FILE_SIZE`] otherwise.
+    #[must_use]
+    pub fn max_lock_file_size(mut self, max_size: u64) -> Self {
+        self.max_lock_file_size = Some(max_size);
+        self
+    }
+
+    pub fn get_or_load(
+        &mut self,
+        lock: &LockFileResult,
+    ) -> Result<Arc<DependencyVersionResolver>, Box<dyn Error>> {
+        Ok(self.load(lock)?.resolver)
+    }
+
+    /// The parsed lock file the resolver of [`ResolverCache::get_or_load`] was built from.
+    pub fn get_or_load_lock(
+        &mut self,
+        lock: &LockFileResult,
+    ) -> Result<Arc<PackageManagerLock>, Box<dyn Error>> {
+        Ok(self.load(lock)?.lock)
+    }
+
+    fn load(&mut self, lock: &LockFileResult) -> Result<CachedLock, Box<dyn Error>> {
+        let key = lock
+            .path
+            .canonicalize()
+            .unwrap_or_else(|_| lock.path.clone());
+        let stamp = lock_stamp(&key);
+        match self.locks.get(&key) {
+            Some(cached) if cached.stamp == stamp => {
+                debug!("Reusing resolver of {}.", key.display());
+                return Ok(cached.clone());
+            }
+            Some(_) => debug!("{} changed since it was parsed.", key.display()),
+            None => {}
+        }
+
+        let max_size = self
+            .max_lock_file_size
+            .unwrap_or(parser::DEFAULT_MAX_LOCK_FILE_SIZE);
+        let lock = if self.allow_unknown_lock_version {
+            parser::parse_lock_allowing_unknown_version(lock, max_size)?
+        } else {
+            parser::parse_lock(lock, max_size)?
+        };
+        let cached = CachedLock {
+            stamp,
+            resolver: Arc::new(build_resolver(&lock)),
+            lock: Arc::new(lock),
+        };
+        self.loads += 1;
+        self.locks.insert(key, cached.clone());
+
+        Ok(cached)
+    }
+
+    /// Number of lock files read from disk so far.
+    pub fn loads(&self) -> usize {
+        self.loads
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{fixture_path, parse_fixture_lock, temp_dir};
+    use crate::types::PackageManager;
+
+    #[test]
+    fn npm_v3_lock_keeps_dependency_and_root_engines() {
+        let lock = parse_fixture_lock("npm-v3-engines", PackageManager::Npm);
+        let PackageManagerLock::Npm(npm_lock) = lock else {
+            panic!("Expected a npm lock file");
+        };
+
+        let root_engines = convert_npm_engines_to_object_engines(npm_lock.root_engines().cloned())
+            .expect("Expected root engines");
+        assert_eq!(root_engines.get(&Engine::Node), Some(&">=18".to_string()));
+
+        let resolver = npm_resolver(&npm_lock);
+        assert!(!resolver.locked_dependencies.contains_key(""));
+        let lodash = resolver
+            .locked_dependencies
+            .get(&(resolver.resolve_dependency_key)("lodash", "^4.17.0"))
+            .expect("Expected lodash to be resolved");
+        assert_eq!(lodash.version, "4.17.21");
+        assert_eq!(
+            lodash
+                .engines
+                .as_ref()
+                .and_then(|engines| engines.get(&Engine::Node)),
+            Some(&">=4".to_string())
+        );
+    }
+
+    #[test]
+    fn npm_v1_lock_resolves_nested_dependencies() {
+        let PackageManagerLock::Npm(npm_lock) =
+            parse_fixture_lock("npm-v1-nested", PackageManager::Npm)
+        else {
+            panic!("Expected a npm lock file");
+        };
+
+        let resolver = npm_resolver(&npm_lock);
+        let resolve = |name| {
+            resolver
+                .locked_dependencies
+                .get(&(resolver.resolve_dependency_key)(name, "*"))
+                .map(|dependency| dependency.version.as_str())
+        };
+
+        assert_eq!(resolve("lodash"), Some("4.17.21"));
+        assert_eq!(resolve("legacy-lib"), Some("1.2.0"));
+        assert_eq!(resolve("nested-only"), Some("2.0.1"));
+    }
+
+    #[test]
+    fn build_resolver_for_every_package_manager() {
+        let tests = [
+            ("npm-sections", PackageManager::Npm, "lodash", "4.17.21"),
+            ("npm-v1-nested", PackageManager::Npm, "lodash", "4.17.21"),
+            (
+                "yarn-berry-ranges",
+                PackageManager::Yarn,
+                "lodash",
+                "4.17.21",
+            ),
+            (
+                "pnpm-v5-specifiers",
+                PackageManager::Pnpm,
+                "lodash",
+                "4.17.21",
+            ),
+            (
+                "pnpm-v6-peer-suffix",
+                PackageManager::Pnpm,
+                "react-dom",
+                "18.2.0",
+            ),
+            (
+                "bun-text-lock",
+                PackageManager::Bun,
+                "@types/node",
+                "20.10.5",
+            ),
+        ];
+
+        for (name, package_manager, dependency, version) in tests {
+            let resolver = build_resolver(&parse_fixture_lock(name, package_manager));
+
+            assert_eq!(
+                resolver
+                    .resolve(dependency, "*")
+                    .map(|dependency| dependency.version.as_str()),
+                Some(version),
+                "{name}"
+            );
+            assert!(resolver.lockfile_version.is_some(), "{name}");
+        }
+    }
+
+    #[test]
+    fn npm_lock_resolves_shallowest_nested_package() {
+        let PackageManagerLock::Npm(npm_lock) =
+            parse_fixture_lock("npm-nested-packages", PackageManager::Npm)
+        else {
+            panic!("Expected a npm lock file");
+        };
+
+        let resolver = npm_resolver(&npm_lock);
+        let resolve = |name| {
+            resolver
+                .resolve(name, "*")
+                .map(|dependency| dependency.version.as_str())
+        };
+
+        assert_eq!(resolve("chalk"), Some("5.3.0"));
+        assert_eq!(resolve("lodash"), Some("4.17.21"));
+        assert_eq!(resolve("legacy"), None);
+    }
+
+    #[test]
+    fn yarn_lock_resolves_by_name_regardless_of_descriptor() {
+        let PackageManagerLock::Yarn(yarn_lock) =
+            parse_fixture_lock("yarn-berry-ranges", PackageManager::Yarn)
+        else {
+            panic!("Expected a yarn lock file");
+        };
+
+        let resolver = yarn_resolver(&yarn_lock);
+        let resolve = |name, version| {
+            resolver
+                .resolve(name, version)
+                .map(|dependency| dependency.version.as_str())
+        };
+
+        assert_eq!(resolve("lodash", "^4.17.0"), Some("4.17.21"));
+        assert_eq!(resolve("@types/node", "^20.10"), Some("20.10.5"));
+        assert_eq!(resolve("yarn-berry-ranges", "*"), None);
+    }
+
+    #[test]
+    fn pnpm_v5_lock_resolves_exact_versions() {
+        let PackageManagerLock::Pnpm(pnpm_lock) =
+            parse_fixture_lock("pnpm-v5-specifiers", PackageManager::Pnpm)
+        else {
+            panic!("Expected a pnpm lock file");
+        };
+
+        let resolver = pnpm_resolver(&pnpm_lock);
+        let resolve = |name| {
+            resolver
+                .locked_dependencies
+                .get(name)
+                .map(|dependency| dependency.version.as_str())
+        };
+
+        assert_eq!(resolve("lodash"), Some("4.17.21"));
+        assert_eq!(resolve("react-dom"), Some("18.2.0"));
+        assert_eq!(resolve("prettier"), Some("3.1.1"));
+    }
+
+    #[test]
+    fn pnpm_lock_skips_empty_versions() {
+        let resolver = build_resolver(&parse_fixture_lock(
+            "pnpm-empty-version",
+            PackageManager::Pnpm,
+        ));
+
+        assert_eq!(
+            resolver.locked_versions(),
+            BTreeMap::from([("lodash", "4.17.21")])
+        );
+    }
+
+    #[test]
+    fn yarn_lock_resolves_the_declared_major_of_packages_locked_twice() {
+        let resolver = build_resolver(&parse_fixture_lock(
+            "yarn-berry-majors",
+            PackageManager::Yarn,
+        ));
+        let resolve = |version| {
+            resolver
+                .resolve("lodash", version)
+                .map(|dependency| dependency.version.as_str())
+        };
+
+        assert_eq!(resolve("^3.10.0"), Some("3.10.1"));
+        assert_eq!(resolve("npm:^4.17.21"), Some("4.17.21"));
+        assert_eq!(resolve("~3.10.1"), Some("3.10.1"));
+        assert_eq!(resolve(">=3"), Some("4.17.21"));
+        assert_eq!(resolve("^5.0.0"), None);
+    }
+
+    #[test]
+    fn yarn_berry_lock_skips_workspace_entries() {
+        let resolver = build_resolver(&parse_fixture_lock(
+            "yarn-berry-workspace",
+            PackageManager::Yarn,
+        ));
+
+        assert_eq!(
+            resolver.locked_versions(),
+            BTreeMap::from([("lodash", "4.17.21"), ("typescript", "5.3.3")])
+        );
+    }
+
+    #[test]
+    fn bun_resolver_reads_hoisted_packages() {
+        let resolver = build_resolver(&parse_fixture_lock("bun-text-lock", PackageManager::Bun));
+        let resolve = |name| {
+            resolver
+                .locked_dependencies
+                .get(name)
+                .map(|dependency| dependency.version.as_str())
+        };
+
+        assert_eq!(resolve("@types/node"), Some("20.10.5"));
+        assert_eq!(resolve("lodash"), Some("4.17.21"));
+        assert_eq!(resolve("typescript"), Some("5.3.3"));
+        assert_eq!(resolve("legacy-tool/@types/node"), None);
+    }
+
+    #[test]
+    fn resolver_cache_reloads_changed_locks() {
+        let dir = temp_dir("resolver-cache-changed");
+        let lock = LockFileResult {
+            path: dir.join("package-lock.json"),
+            package_manager: PackageManager::Npm,
+        };
+        let write_lock = |version: &str| {
+            std::fs::write(
+                &lock.path,
+                format!(
+                    r#"{{ "lockfileVersion": 3, "packages": {{ "node_modules/lodash": {{ "version": "{version}" }} }} }}"#
+                ),
+            )
+            .expect("Unable to write lock file");
+        };
+        let locked_lodash = |cache: &mut ResolverCache| {
+            cache
+                .get_or_load(&lock)
+                .expect("Unable to load resolver")
+                .locked_dependencies["node_modules/lodash"]
+                .version
+                .clone()
+        };
+        let mut cache = ResolverCache::default();
+
+        write_lock("4.17.20");
+        assert_eq!(locked_lodash(&mut cache), "4.17.20");
+        assert_eq!(locked_lodash(&mut cache), "4.17.20");
+        assert_eq!(cache.loads(), 1);
+
+        write_lock("4.2.0");
+        assert_eq!(locked_lodash(&mut cache), "4.2.0");
+        assert_eq!(cache.loads(), 2);
+    }
+
+    #[test]
+    fn yarn_descriptor_names() {
+        assert_eq!(yarn_descriptor_name("lodash@npm:^4.17.0"), Some("lodash"));
+        assert_eq!(
+            yarn_descriptor_name(" \"@types/node@npm:^20.10.0\""),
+            Some("@types/node")
+        );
+        assert_eq!(yarn_descriptor_name("project@workspace:."), None);
+        assert_eq!(yarn_descriptor_name("__metadata"), None);
+    }
+
+    #[test]
+    fn resolver_cache_reads_each_lock_once() {
+        let lock = |path: PathBuf| LockFileResult {
+            path,
+            package_manager: PackageManager::Npm,
+        };
+        let root_lock = fixture_path("npm-workspace").join("package-lock.json");
+        let member_lock = fixture_path("npm-workspace")
+            .join("packages/a/../..")
+            .join("package-lock.json");
+        let mut cache = ResolverCache::default();
+
+        for _ in 0..50 {
+            cache
+                .get_or_load(&lock(root_lock.clone()))
+                .expect("Unable to load resolver");
+            cache
+                .get_or_load(&lock(member_lock.clone()))
+                .expect("Unable to load resolver");
+        }
+        assert_eq!(cache.loads(), 1);
+
+        cache
+            .get_or_load(&lock(
+                fixture_path("npm-sections").join("package-lock.json"),
+            ))
+            .expect("Unable to load resolver");
+        assert_eq!(cache.loads(), 2);
+    }
+
+    #[test]
+    fn resolver_cache_shares_the_parsed_lock() {
+        let lock = LockFileResult {
+            path: fixture_path("npm-engines-mismatch").join("package-lock.json"),
+            package_manager: PackageManager::Npm,
+        };
+        let mut cache = ResolverCache::default();
+
+        cache.get_or_load(&lock).expect("Unable to load resolver");
+        let parsed = cache.get_or_load_lock(&lock).expect("Unable to load lock");
+
+        assert!(matches!(*parsed, PackageManagerLock::Npm(_)));
+        assert_eq!(cache.loads(), 1);
+    }
+}
@@ -0,0 +1,40 @@
+use crate::parser;
+use crate::types::{LockFileResult, PackageJson, PackageManager, PackageManagerLock};
+use std::path::PathBuf;
+
+pub fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("riri-node-tools-{name}"));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("Unable to create temporary directory");
+    dir
+}
+
+pub fn parse_fixture_package(name: &str) -> PackageJson {
+    let (package_json, _, _) = parser::parse_package(&fixture_path(name).join("package.json"))
+        .expect("Unable to parse package.json fixture");
+    package_json
+}
+
+pub fn parse_fixture_lock(name: &str, package_manager: PackageManager) -> PackageManagerLock {
+    let lock_file_name = match package_manager {
+        PackageManager::Npm => "package-lock.json",
+        PackageManager::Yarn => "yarn.lock",
+        PackageManager::Pnpm => "pnpm-lock.yaml",
+        PackageManager::Bun => "bun.lock",
+    };
+
+    parser::parse_lock(
+        &LockFileResult {
+            path: fixture_path(name).join(lock_file_name),
+            package_manager,
+        },
+        parser::DEFAULT_MAX_LOCK_FILE_SIZE,
+    )
+    .expect("Unable to parse lock file fixture")
+}
@@ -1,174 +1,868 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
-
-#[derive(Debug, Clone)]
-pub enum PackageManager {
-    Npm,
-    Yarn,
-    Pnpm,
-}
-
-#[derive(Debug, Clone)]
-pub struct LockFileResult {
-    pub path: PathBuf,
-    pub package_manager: PackageManager,
-}
-
-pub type Dependencies = HashMap<String, String>;
-
-#[derive(Debug, Deserialize, Clone, Serialize)]
-#[serde(rename_all(deserialize = "camelCase", serialize = "camelCase"))]
-pub struct PackageJson {
-    pub name: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(default)]
-    pub dependencies: Option<Dependencies>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(default)]
-    pub dev_dependencies: Option<Dependencies>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(default)]
-    pub optional_dependencies: Option<Dependencies>,
-}
-
-#[derive(Debug, Deserialize, Clone, PartialEq, Eq, Hash)]
-pub enum Engine {
-    Node,
-    Npm,
-    Yarn,
-}
-
-pub type ObjectEngines = HashMap<Engine, String>;
-
-#[derive(Debug, Deserialize, Clone)]
-pub struct LockDependency {
-    pub version: String,
-    #[serde(default)]
-    pub engines: Option<ObjectEngines>,
-}
-
-#[derive(Debug, Deserialize, Clone)]
-#[serde(untagged)]
-pub enum NpmLockEngines {
-    Object(ObjectEngines),
-    Array(Vec<String>),
-}
-
-#[derive(Debug, Deserialize, Clone)]
-pub struct VersionedDependency {
-    pub version: String,
-    #[serde(default)]
-    pub engines: Option<NpmLockEngines>,
-}
-
-#[derive(Debug, Deserialize, Clone)]
-#[serde(rename_all(deserialize = "camelCase"))]
-pub struct ResolvedDependency {
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub resolved: Option<String>,
-    pub link: bool,
-    #[serde(default)]
-    pub engines: Option<NpmLockEngines>,
-}
-
-#[derive(Debug, Deserialize, Clone)]
-#[serde(untagged)]
-pub enum VersionedDependencyOrResolved {
-    Versioned(VersionedDependency),
-    Resolved(ResolvedDependency),
-}
-
-pub type NpmDependencies = HashMap<String, VersionedDependencyOrResolved>;
-type NpmLockDependencies = NpmDependencies;
-type NpmLockPackages = NpmDependencies;
-
-#[derive(Debug, Deserialize, Clone)]
-#[serde(rename_all(deserialize = "camelCase"))]
-pub struct NpmLockVersion1 {
-    pub lockfile_version: u8,
-    #[serde(default)]
-    pub dependencies: NpmLockDependencies,
-}
-
-#[derive(Debug, Deserialize, Clone)]
-#[serde(rename_all(deserialize = "camelCase"))]
-pub struct NpmLockVersion2 {
-    pub lockfile_version: u8,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub packages: Option<NpmLockPackages>,
-    #[serde(default)]
-    pub dependencies: NpmLockDependencies,
-}
-
-#[derive(Debug, Deserialize, Clone)]
-#[serde(rename_all(deserialize = "camelCase"))]
-pub struct NpmLockVersion3 {
-    pub lockfile_version: u8,
-    #[serde(default)]
-    pub packages: NpmLockPackages,
-}
-
-#[derive(Debug, Deserialize, Clone)]
-#[serde(rename_all(deserialize = "camelCase"))]
-#[serde(untagged)]
-pub enum NpmLock {
-    Version1(NpmLockVersion1),
-    Version2(NpmLockVersion2),
-    Version3(NpmLockVersion3),
-}
-
-#[derive(Debug, Deserialize, Clone)]
-#[serde(rename_all(deserialize = "camelCase"))]
-pub struct FirstLevelDependency {
-    pub version: String,
-    pub resolved: Option<String>,
-    pub dependencies: Option<HashMap<String, String>>,
-}
-
-pub type YarnLockV2 = HashMap<String, FirstLevelDependency>;
-
-#[derive(Debug, Deserialize, Clone)]
-#[serde(rename_all(deserialize = "camelCase"))]
-pub struct PnpmImporterV5 {
-    pub dependencies: Option<HashMap<String, String>>,
-    pub optional_dependencies: Option<HashMap<String, String>>,
-    pub dev_dependencies: Option<HashMap<String, String>>,
-}
-
-#[derive(Debug, Deserialize, Clone)]
-#[serde(rename_all(deserialize = "camelCase"))]
-pub struct PnpmLockV5 {
-    pub lockfile_version: String,
-    pub importers: HashMap<String, PnpmImporterV5>,
-}
-
-#[derive(Debug, Deserialize, Clone)]
-#[serde(rename_all(deserialize = "camelCase"))]
-pub struct PnpmImporterV6 {
-    pub dependencies: Option<HashMap<String, LockDependency>>,
-    pub optional_dependencies: Option<HashMap<String, LockDependency>>,
-    pub dev_dependencies: Option<HashMap<String, LockDependency>>,
-}
-
-#[derive(Debug, Deserialize, Clone)]
-#[serde(rename_all(deserialize = "camelCase"))]
-pub struct PnpmLockV6 {
-    pub lockfile_version: String,
-    pub importers: HashMap<String, PnpmImporterV6>,
-}
-
-#[derive(Debug, Deserialize, Clone)]
-#[serde(untagged)]
-pub enum PnpmLock {
-    Version5(PnpmLockV5),
-    Version6(PnpmLockV6),
-}
-
-#[derive(Debug, Deserialize, Clone)]
-#[serde(untagged)]
-pub enum PackageManagerLock {
-    Npm(NpmLock),
-    Yarn(YarnLockV2),
-    Pnpm(PnpmLock),
-}
+use crate::pin::PinStyle;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PackageManager {
+    Npm,
+    Yarn,
+    Pnpm,
+    Bun,
+}
+
+pub const NPM_LOCK_FILE: &str = "package-lock.json";
+pub const YARN_LOCK_FILE: &str = "yarn.lock";
+pub const PNPM_LOCK_FILE: &str = "pnpm-lock.yaml";
+pub const BUN_LOCK_FILE: &str = "bun.lock";
+
+impl PackageManager {
+    pub fn from_lock_filename(name: &str) -> Option<PackageManager> {
+        match name {
+            NPM_LOCK_FILE => Some(PackageManager::Npm),
+            YARN_LOCK_FILE => Some(PackageManager::Yarn),
+            PNPM_LOCK_FILE => Some(PackageManager::Pnpm),
+            BUN_LOCK_FILE => Some(PackageManager::Bun),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PackageManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PackageManager::Npm => "npm",
+            PackageManager::Yarn => "yarn",
+            PackageManager::Pnpm => "pnpm",
+            PackageManager::Bun => "bun",
+        })
+    }
+}
+
+impl FromStr for PackageManager {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "npm" => Ok(PackageManager::Npm),
+            "yarn" => Ok(PackageManager::Yarn),
+            "pnpm" => Ok(PackageManager::Pnpm),
+            "bun" => Ok(PackageManager::Bun),
+            _ => Err(format!(
+                "unknown package manager {value:?}, expected one of npm, yarn, pnpm or bun"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LockFileResult {
+    pub path: PathBuf,
+    pub package_manager: PackageManager,
+}
+
+/// Object form some tooling writes instead of a version string, e.g. `{ "version": "^1.0.0" }`.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq, Serialize)]
+pub struct DependencyObject {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub npm: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum DependencySpecifier {
+    Version(String),
+    Object(DependencyObject),
+}
+
+impl DependencySpecifier {
+    /// The version range, for the string form only: object forms are never pinned.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            DependencySpecifier::Version(version) => Some(version),
+            DependencySpecifier::Object(_) => None,
+        }
+    }
+}
+
+pub type Dependencies = HashMap<String, DependencySpecifier>;
+
+/// The parts of a `deno.json` or `jsr.json` manifest used for pinning.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct DenoJson {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub imports: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(rename_all(deserialize = "camelCase", serialize = "camelCase"))]
+pub struct PackageJson {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub dependencies: Option<Dependencies>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub dev_dependencies: Option<Dependencies>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub optional_dependencies: Option<Dependencies>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub overrides: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub resolutions: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, alias = "bundledDependencies")]
+    pub bundle_dependencies: Option<BundleDependencies>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub package_manager: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub engines: Option<NpmLockEngines>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub workspaces: Option<Workspaces>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Workspaces {
+    Packages(Vec<String>),
+    Object {
+        #[serde(default)]
+        packages: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(default)]
+        nohoist: Option<Vec<String>>,
+    },
+}
+
+impl Workspaces {
+    /// Glob patterns of the workspace members, whichever form is used.
+    pub fn packages(&self) -> &[String] {
+        match self {
+            Workspaces::Packages(packages) | Workspaces::Object { packages, .. } => packages,
+        }
+    }
+}
+
+impl PackageJson {
+    /// Package manager declared through the corepack `packageManager` field, e.g. `yarn@4.1.0`.
+    pub fn declared_package_manager(&self) -> Option<PackageManager> {
+        let package_manager = self.package_manager.as_deref()?;
+        let name = package_manager
+            .split_once('@')
+            .map_or(package_manager, |(name, _)| name);
+
+        PackageManager::from_str(name).ok()
+    }
+
+    /// Every dependency section, declared or not, in the order npm reads them.
+    pub fn dependency_sections(&self) -> [(DependencySection, Option<&Dependencies>); 3] {
+        [
+            (DependencySection::Dependencies, self.dependencies.as_ref()),
+            (
+                DependencySection::DevDependencies,
+                self.dev_dependencies.as_ref(),
+            ),
+            (
+                DependencySection::OptionalDependencies,
+                self.optional_dependencies.as_ref(),
+            ),
+        ]
+    }
+
+    /// Declared specifier and section of every dependency, across sections. A dependency declared
+    /// in several sections keeps its first one, object specifiers are left out.
+    pub fn all_dependencies(&self) -> HashMap<&str, (&str, DependencySection)> {
+        let mut all_dependencies = HashMap::new();
+        for (section, dependencies) in self.dependency_sections() {
+            for (name, specifier) in dependencies.into_iter().flatten() {
+                if let Some(version) = specifier.as_str() {
+                    all_dependencies
+                        .entry(name.as_str())
+                        .or_insert((version, section));
+                }
+            }
+        }
+
+        all_dependencies
+    }
+
+    /// Dependencies declared in more than one section, with every section declaring them.
+    pub fn dependencies_in_several_sections(&self) -> BTreeMap<&str, Vec<DependencySection>> {
+        let mut sections_per_dependency: BTreeMap<&str, Vec<DependencySection>> = BTreeMap::new();
+        for (section, dependencies) in self.dependency_sections() {
+            for name in dependencies.into_iter().flat_map(HashMap::keys) {
+                sections_per_dependency
+                    .entry(name)
+                    .or_default()
+                    .push(section);
+            }
+        }
+
+        sections_per_dependency.retain(|_, sections| sections.len() > 1);
+        sections_per_dependency
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(untagged)]
+pub enum BundleDependencies {
+    Names(Vec<String>),
+    All(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DependencySection {
+    Dependencies,
+    DevDependencies,
+    OptionalDependencies,
+}
+
+impl fmt::Display for DependencySection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DependencySection::Dependencies => write!(f, "dependencies"),
+            DependencySection::DevDependencies => write!(f, "devDependencies"),
+            DependencySection::OptionalDependencies => write!(f, "optionalDependencies"),
+        }
+    }
+}
+
+/// Key of an `engines` field. Keys other than node, npm and yarn (`vscode`, `pnpm`...) keep their
+/// name, so they neither overwrite each other nor get written back under another one.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Engine {
+    Node,
+    Npm,
+    Yarn,
+    Other(String),
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Engine::Node => write!(f, "node"),
+            Engine::Npm => write!(f, "npm"),
+            Engine::Yarn => write!(f, "yarn"),
+            Engine::Other(name) => f.write_str(name),
+        }
+    }
+}
+
+impl From<String> for Engine {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "node" => Engine::Node,
+            "npm" => Engine::Npm,
+            "yarn" => Engine::Yarn,
+            _ => Engine::Other(name),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Engine {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Engine::from)
+    }
+}
+
+impl Serialize for Engine {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+pub type ObjectEngines = HashMap<Engine, String>;
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct LockDependency {
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engines: Option<ObjectEngines>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(untagged)]
+pub enum NpmLockEngines {
+    Object(ObjectEngines),
+    Array(Vec<String>),
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct VersionedDependency {
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engines: Option<NpmLockEngines>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedDependency {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<String>,
+    pub link: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engines: Option<NpmLockEngines>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct UnversionedDependency {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engines: Option<NpmLockEngines>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(untagged)]
+pub enum VersionedDependencyOrResolved {
+    Versioned(VersionedDependency),
+    Resolved(ResolvedDependency),
+    Unversioned(UnversionedDependency),
+}
+
+pub type NpmDependencies = HashMap<String, VersionedDependencyOrResolved>;
+type NpmLockDependencies = HashMap<String, NpmLockTreeDependency>;
+type NpmLockPackages = NpmDependencies;
+
+/// An entry of the npm v1 `dependencies` tree, nesting the dependencies that could not be hoisted.
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct NpmLockTreeDependency {
+    #[serde(flatten)]
+    pub dependency: VersionedDependencyOrResolved,
+    #[serde(default)]
+    pub dependencies: NpmLockDependencies,
+}
+
+/// Flattens a v1 `dependencies` tree by name, shallower entries winning over nested ones.
+pub fn flatten_npm_dependencies_tree(tree: &NpmLockDependencies) -> NpmDependencies {
+    let mut flattened = NpmDependencies::new();
+    let mut level = vec![tree];
+
+    while !level.is_empty() {
+        let mut next_level = Vec::new();
+        for dependencies in level {
+            for (name, entry) in dependencies {
+                flattened
+                    .entry(name.clone())
+                    .or_insert_with(|| entry.dependency.clone());
+                next_level.push(&entry.dependencies);
+            }
+        }
+        level = next_level;
+    }
+
+    flattened
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NpmLockVersion1 {
+    pub lockfile_version: u8,
+    #[serde(default)]
+    pub dependencies: NpmLockDependencies,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NpmLockVersion2 {
+    pub lockfile_version: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packages: Option<NpmLockPackages>,
+    #[serde(default)]
+    pub dependencies: NpmLockDependencies,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NpmLockVersion3 {
+    pub lockfile_version: u8,
+    #[serde(default)]
+    pub packages: NpmLockPackages,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+#[serde(untagged)]
+pub enum NpmLock {
+    Version1(NpmLockVersion1),
+    Version2(NpmLockVersion2),
+    Version3(NpmLockVersion3),
+}
+
+const NPM_ROOT_PACKAGE: &str = "";
+
+impl NpmLock {
+    pub fn root_engines(&self) -> Option<&NpmLockEngines> {
+        let packages = match self {
+            NpmLock::Version1(_) => None,
+            NpmLock::Version2(lock) => lock.packages.as_ref(),
+            NpmLock::Version3(lock) => Some(&lock.packages),
+        };
+
+        match packages?.get(NPM_ROOT_PACKAGE)? {
+            VersionedDependencyOrResolved::Versioned(root) => root.engines.as_ref(),
+            VersionedDependencyOrResolved::Resolved(root) => root.engines.as_ref(),
+            VersionedDependencyOrResolved::Unversioned(root) => root.engines.as_ref(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FirstLevelDependency {
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<String>,
+    /// Yarn Berry locator the entry resolves to, e.g. `lodash@npm:4.17.21` or
+    /// `app@workspace:packages/app`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dependencies: Option<HashMap<String, String>>,
+}
+
+pub type YarnLockV2 = HashMap<String, FirstLevelDependency>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YarnLockVersion {
+    V1,
+    Berry(u32),
+}
+
+#[derive(Debug, Deserialize, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PnpmImporterV5 {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub specifiers: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dependencies: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub optional_dependencies: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dev_dependencies: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PnpmLockV5 {
+    pub lockfile_version: String,
+    #[serde(default)]
+    pub importers: HashMap<String, PnpmImporterV5>,
+    #[serde(default)]
+    pub packages: HashMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PnpmImporterV6 {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dependencies: Option<HashMap<String, LockDependency>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub optional_dependencies: Option<HashMap<String, LockDependency>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dev_dependencies: Option<HashMap<String, LockDependency>>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PnpmLockV6 {
+    pub lockfile_version: String,
+    #[serde(default)]
+    pub importers: HashMap<String, PnpmImporterV6>,
+    #[serde(default)]
+    pub packages: HashMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(untagged)]
+pub enum PnpmLock {
+    Version5(PnpmLockV5),
+    Version6(PnpmLockV6),
+}
+
+/// Text lock file written by Bun since 1.2. Every `packages` entry is an array starting with
+/// `name@version`, keyed by the package name, or by `parent/name` when nested.
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BunLock {
+    pub lockfile_version: u32,
+    #[serde(default)]
+    pub packages: HashMap<String, Vec<Value>>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(untagged)]
+pub enum PackageManagerLock {
+    Npm(NpmLock),
+    Yarn(YarnLockV2),
+    Pnpm(PnpmLock),
+    Bun(BunLock),
+}
+
+impl PackageManagerLock {
+    /// Format version the lock file declares, as it was parsed.
+    pub fn version(&self) -> LockfileVersion {
+        match self {
+            PackageManagerLock::Npm(NpmLock::Version1(lock)) => {
+                LockfileVersion::Npm(lock.lockfile_version)
+            }
+            PackageManagerLock::Npm(NpmLock::Version2(lock)) => {
+                LockfileVersion::Npm(lock.lockfile_version)
+            }
+            PackageManagerLock::Npm(NpmLock::Version3(lock)) => {
+                LockfileVersion::Npm(lock.lockfile_version)
+            }
+            PackageManagerLock::Yarn(lock) => LockfileVersion::Yarn(
+                lock.get("__metadata")
+                    .and_then(|metadata| metadata.version.parse().ok()),
+            ),
+            PackageManagerLock::Pnpm(PnpmLock::Version5(lock)) => {
+                LockfileVersion::Pnpm(lock.lockfile_version.clone())
+            }
+            PackageManagerLock::Pnpm(PnpmLock::Version6(lock)) => {
+                LockfileVersion::Pnpm(lock.lockfile_version.clone())
+            }
+            PackageManagerLock::Bun(lock) => LockfileVersion::Bun(lock.lockfile_version),
+        }
+    }
+}
+
+/// `lockfileVersion` of npm, pnpm and Bun lock files, `__metadata` version of yarn Berry ones,
+/// missing from an empty yarn lock file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "packageManager", content = "version")]
+pub enum LockfileVersion {
+    Npm(u8),
+    Yarn(Option<u32>),
+    Pnpm(String),
+    Bun(u32),
+}
+
+impl fmt::Display for LockfileVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockfileVersion::Npm(version) => write!(f, "npm lockfileVersion {version}"),
+            LockfileVersion::Yarn(Some(version)) => write!(f, "yarn metadata version {version}"),
+            LockfileVersion::Yarn(None) => f.write_str("yarn without metadata"),
+            LockfileVersion::Pnpm(version) => write!(f, "pnpm lockfileVersion {version}"),
+            LockfileVersion::Bun(version) => write!(f, "bun lockfileVersion {version}"),
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct Npmrc {
+    pub registry: Option<String>,
+    pub scoped_registries: HashMap<String, String>,
+    pub auth_tokens: HashMap<String, String>,
+    /// `engine-strict=true`, npm then refuses to install packages whose engines do not match.
+    pub engine_strict: bool,
+}
+
+impl fmt::Debug for Npmrc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redacted_auth_tokens: HashMap<&String, &str> =
+            self.auth_tokens.keys().map(|key| (key, "***")).collect();
+
+        f.debug_struct("Npmrc")
+            .field("registry", &self.registry)
+            .field("scoped_registries", &self.scoped_registries)
+            .field("auth_tokens", &redacted_auth_tokens)
+            .field("engine_strict", &self.engine_strict)
+            .finish()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all(deserialize = "kebab-case"))]
+pub enum YarnNodeLinker {
+    #[default]
+    Pnp,
+    Pnpm,
+    NodeModules,
+}
+
+impl fmt::Display for YarnNodeLinker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            YarnNodeLinker::Pnp => "pnp",
+            YarnNodeLinker::Pnpm => "pnpm",
+            YarnNodeLinker::NodeModules => "node-modules",
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct Yarnrc {
+    #[serde(default)]
+    pub node_linker: Option<YarnNodeLinker>,
+    #[serde(default)]
+    pub nm_mode: Option<String>,
+}
+
+impl Yarnrc {
+    /// Linker in use, yarn Berry defaulting to Plug'n'Play when `nodeLinker` is unset.
+    pub fn node_linker(&self) -> YarnNodeLinker {
+        self.node_linker.unwrap_or_default()
+    }
+}
+
+/// Defaults read from `.npdrc`, keyed like the `pin-dependencies` flags they stand for
+/// (`pinStyle` for `--pin-style`). Unknown keys are rejected so a typo does not go unnoticed.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(rename_all(deserialize = "camelCase"), default, deny_unknown_fields)]
+pub struct NpdConfig {
+    pub update: Option<bool>,
+    pub respect_overrides: Option<bool>,
+    pub apply_resolutions: Option<bool>,
+    pub prod: Option<bool>,
+    pub ignore_optional: Option<bool>,
+    pub allow_downgrade: Option<bool>,
+    pub dev_scope: Option<Vec<String>>,
+    pub ignore: Option<Vec<String>>,
+    pub ignore_file: Option<PathBuf>,
+    pub strip_prefix: Option<bool>,
+    pub pin_style: Option<PinStyle>,
+    pub dedupe_write: Option<bool>,
+    pub summary_only: Option<bool>,
+    pub group_by_scope: Option<bool>,
+    pub timings: Option<bool>,
+    pub report_file: Option<PathBuf>,
+    pub dedupe_report: Option<bool>,
+    /// `table`, `json` or `ndjson`, checked by the binary which owns the output formats.
+    pub format: Option<String>,
+    pub allow_unknown_lockfile_version: Option<bool>,
+    pub max_lockfile_size: Option<u64>,
+    pub max_depth: Option<usize>,
+    pub stop_at_git_root: Option<bool>,
+    pub workspaces: Option<bool>,
+    pub threads: Option<NonZeroUsize>,
+    pub member_locks: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{parse_fixture_lock, parse_fixture_package};
+
+    #[test]
+    fn package_manager_from_lock_filename() {
+        let tests = [
+            (NPM_LOCK_FILE, Some(PackageManager::Npm)),
+            (YARN_LOCK_FILE, Some(PackageManager::Yarn)),
+            (PNPM_LOCK_FILE, Some(PackageManager::Pnpm)),
+            (BUN_LOCK_FILE, Some(PackageManager::Bun)),
+            ("bun.lockb", None),
+            ("package.json", None),
+            ("pnpm-lock.yml", None),
+        ];
+
+        for (name, expected) in tests {
+            assert_eq!(PackageManager::from_lock_filename(name), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn declared_package_manager() {
+        let tests = [
+            (r#"{ "name": "app" }"#, None),
+            (
+                r#"{ "name": "app", "packageManager": "yarn@4.1.0" }"#,
+                Some(PackageManager::Yarn),
+            ),
+            (
+                r#"{ "name": "app", "packageManager": "pnpm@8.15.4+sha256.abc" }"#,
+                Some(PackageManager::Pnpm),
+            ),
+            (
+                r#"{ "name": "app", "packageManager": "bun@1.2.0" }"#,
+                Some(PackageManager::Bun),
+            ),
+            (r#"{ "name": "app", "packageManager": "deno@2.0.0" }"#, None),
+        ];
+
+        for (package_json, expected) in tests {
+            let package_json: PackageJson =
+                serde_json::from_str(package_json).expect("Unable to parse package.json");
+            assert_eq!(
+                package_json.declared_package_manager(),
+                expected,
+                "{package_json:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn dependencies_declared_in_several_sections() {
+        assert_eq!(
+            parse_fixture_package("npm-optional-in-prod")
+                .dependencies_in_several_sections()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![(
+                "fsevents",
+                vec![
+                    DependencySection::Dependencies,
+                    DependencySection::OptionalDependencies
+                ]
+            )]
+        );
+        assert!(parse_fixture_package("npm-sections")
+            .dependencies_in_several_sections()
+            .is_empty());
+    }
+
+    #[test]
+    fn all_dependencies_merge_sections() {
+        let package_json = parse_fixture_package("npm-optional-in-prod");
+        let all_dependencies = package_json.all_dependencies();
+
+        assert_eq!(
+            all_dependencies.get("fsevents"),
+            Some(&("^2.3.0", DependencySection::Dependencies))
+        );
+        assert_eq!(
+            parse_fixture_package("npm-sections").all_dependencies(),
+            HashMap::from([
+                ("lodash", ("^4.17.0", DependencySection::Dependencies)),
+                ("prettier", ("^3.0.0", DependencySection::DevDependencies)),
+                ("typescript", ("~5.3.0", DependencySection::DevDependencies)),
+                (
+                    "fsevents",
+                    ("^2.3.0", DependencySection::OptionalDependencies)
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn package_json_declares_engines() {
+        let Some(NpmLockEngines::Object(engines)) = parse_fixture_package("npm-v3-engines").engines
+        else {
+            panic!("Expected object engines");
+        };
+
+        assert_eq!(engines.get(&Engine::Node), Some(&">=18".to_string()));
+        assert!(parse_fixture_package("npm-sections").engines.is_none());
+    }
+
+    #[test]
+    fn unknown_engines_keep_their_name() {
+        let engines: ObjectEngines =
+            serde_json::from_str(r#"{ "node": ">=18", "pnpm": ">=8", "vscode": "^1.80.0" }"#)
+                .expect("Unable to parse engines");
+
+        assert_eq!(engines.len(), 3);
+        assert_eq!(
+            engines.get(&Engine::Other("vscode".to_string())),
+            Some(&"^1.80.0".to_string())
+        );
+        assert_eq!(
+            serde_json::to_value(&engines).expect("Unable to serialize engines")["pnpm"],
+            ">=8"
+        );
+    }
+
+    #[test]
+    fn workspaces_array_and_object_forms() {
+        let packages = |name| -> Vec<String> {
+            parse_fixture_package(name)
+                .workspaces
+                .expect("Expected workspaces")
+                .packages()
+                .to_vec()
+        };
+
+        assert_eq!(packages("npm-workspace"), vec!["packages/*", "tools/cli"]);
+        assert_eq!(packages("yarn-workspace-object"), vec!["packages/*"]);
+        assert!(parse_fixture_package("npm-sections").workspaces.is_none());
+    }
+
+    #[test]
+    fn lockfile_version_of_fixtures() {
+        let tests = [
+            (
+                "npm-v1-nested",
+                PackageManager::Npm,
+                LockfileVersion::Npm(1),
+            ),
+            ("npm-sections", PackageManager::Npm, LockfileVersion::Npm(3)),
+            (
+                "yarn-berry-ranges",
+                PackageManager::Yarn,
+                LockfileVersion::Yarn(Some(8)),
+            ),
+            (
+                "yarn-empty-lock",
+                PackageManager::Yarn,
+                LockfileVersion::Yarn(None),
+            ),
+            (
+                "pnpm-v5-specifiers",
+                PackageManager::Pnpm,
+                LockfileVersion::Pnpm("5.4".to_string()),
+            ),
+            (
+                "pnpm-v6-peer-suffix",
+                PackageManager::Pnpm,
+                LockfileVersion::Pnpm("6.0".to_string()),
+            ),
+            (
+                "bun-text-lock",
+                PackageManager::Bun,
+                LockfileVersion::Bun(1),
+            ),
+        ];
+
+        for (name, package_manager, version) in tests {
+            assert_eq!(
+                parse_fixture_lock(name, package_manager).version(),
+                version,
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn npm_v3_lock_round_trips_through_serialize() {
+        let lock = parse_fixture_lock("npm-sections", PackageManager::Npm);
+
+        let serialized = serde_json::to_value(&lock).expect("Unable to serialize lock");
+        assert_eq!(serialized["lockfileVersion"], 3);
+        assert_eq!(
+            serialized["packages"]["node_modules/lodash"]["version"],
+            "4.17.21"
+        );
+
+        let reparsed = crate::parser::parse_lock_content(
+            &serde_json::to_string(&serialized).expect("Unable to serialize lock"),
+            PackageManager::Npm,
+        )
+        .expect("Unable to parse serialized lock");
+        assert!(matches!(
+            reparsed,
+            PackageManagerLock::Npm(NpmLock::Version3(_))
+        ));
+        assert_eq!(
+            serde_json::to_value(&reparsed).expect("Unable to serialize lock"),
+            serialized
+        );
+    }
+}
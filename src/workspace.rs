@@ -0,0 +1,333 @@
+use crate::finder::{self, FindUpOptions};
+use crate::parser;
+use crate::pin::{compute_pin_plan, PinOptions, PinPlan};
+use crate::resolver::{DependencyVersionResolver, ResolverCache};
+use crate::types::LockfileVersion;
+use anyhow::{anyhow, Result};
+use detect_indent::Indent;
+use regex::Regex;
+use serde_json::Value;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tracing::debug;
+
+#[derive(Debug)]
+pub struct PackagePins {
+    pub path: PathBuf,
+    pub name: String,
+    pub raw_package: Value,
+    pub indent: Indent,
+    pub pin_plan: PinPlan,
+    pub lockfile_version: Option<LockfileVersion>,
+}
+
+fn segment_regex(segment: &str) -> Regex {
+    let pattern = segment
+        .split('*')
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(".*");
+
+    Regex::new(&format!("^{pattern}$")).expect("Failed to create workspace pattern regex")
+}
+
+fn matching_dirs(dir: &Path, segment: &str) -> Vec<PathBuf> {
+    let segment_regex = segment_regex(segment);
+
+    match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_dir()
+                    && path
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| segment_regex.is_match(name))
+            })
+            .collect(),
+        Err(err) => {
+            debug!("Unable to read directory {}: {}.", dir.display(), err);
+            Vec::new()
+        }
+    }
+}
+
+/// Directories matched by the `workspaces` patterns of the package.json in `root` that
+/// contain a package.json. Only `*` wildcards within a path segment are supported.
+pub fn find_members<T: AsRef<str>>(root: &Path, patterns: &[T]) -> Vec<PathBuf> {
+    let mut members = Vec::new();
+
+    for pattern in patterns {
+        let pattern = pattern.as_ref();
+        if pattern.starts_with('!') || pattern.contains("**") {
+            debug!("Unsupported workspace pattern {}.", pattern);
+            continue;
+        }
+
+        let mut candidates = vec![root.to_path_buf()];
+        for segment in pattern.split('/').filter(|s| !s.is_empty() && *s != ".") {
+            candidates = if segment.contains('*') {
+                candidates
+                    .iter()
+                    .flat_map(|candidate| matching_dirs(candidate, segment))
+                    .collect()
+            } else {
+                candidates
+                    .into_iter()
+                    .map(|candidate| candidate.join(segment))
+                    .collect()
+            };
+        }
+
+        members.extend(
+            candidates
+                .into_iter()
+                .filter(|candidate| candidate.join("package.json").is_file()),
+        );
+    }
+
+    members.sort();
+    members.dedup();
+    members
+}
+
+fn plan_package_pins(
+    dir: &Path,
+    resolver: &DependencyVersionResolver,
+    options: &PinOptions,
+) -> Result<PackagePins> {
+    let path = dir.join("package.json");
+    let (package_json, raw_package, indent) = parser::parse_package(&path)
+        .map_err(|err| anyhow!("Unable to parse {}: {err}", path.display()))?;
+    let pin_plan = compute_pin_plan(&package_json, resolver, options)?;
+
+    Ok(PackagePins {
+        path,
+        name: package_json.name,
+        raw_package,
+        indent,
+        pin_plan,
+        lockfile_version: resolver.lockfile_version.clone(),
+    })
+}
+
+/// Plans the pins of every member against the shared resolver, spreading members over at
+/// most `threads` workers. Results keep the order of `members`.
+pub fn plan_members_pins(
+    members: &[PathBuf],
+    resolver: &DependencyVersionResolver,
+    options: &PinOptions,
+    threads: NonZeroUsize,
+) -> Result<Vec<PackagePins>> {
+    plan_members_pins_with(members, |_| resolver, options, threads)
+}
+
+/// Loads the resolver of the lock file closest to each member, for monorepos mixing package
+/// managers. Members sharing a lock file share its resolver.
+pub fn load_members_resolvers(
+    members: &[PathBuf],
+    options: FindUpOptions,
+    resolver_cache: &mut ResolverCache,
+) -> Result<Vec<Arc<DependencyVersionResolver>>> {
+    members
+        .iter()
+        .map(|member| {
+            let lock =
+                finder::get_most_recently_modified_lock_from(member, options).map_err(|err| {
+                    anyhow!("Unable to get the lock file of {}: {err}", member.display())
+                })?;
+            debug!("Member {} uses {}.", member.display(), lock.path.display());
+            resolver_cache
+                .get_or_load(&lock)
+                .map_err(|err| anyhow!("Unable to parse {}: {err}", lock.path.display()))
+        })
+        .collect()
+}
+
+/// Same as [`plan_members_pins`], each member being resolved by the resolver at the same index
+/// of `resolvers`.
+pub fn plan_members_pins_with_resolvers(
+    members: &[PathBuf],
+    resolvers: &[Arc<DependencyVersionResolver>],
+    options: &PinOptions,
+    threads: NonZeroUsize,
+) -> Result<Vec<PackagePins>> {
+    plan_members_pins_with(members, |index| &resolvers[index], options, threads)
+}
+
+fn plan_members_pins_with<'a>(
+    members: &[PathBuf],
+    resolver_for: impl Fn(usize) -> &'a DependencyVersionResolver + Sync,
+    options: &PinOptions,
+    threads: NonZeroUsize,
+) -> Result<Vec<PackagePins>> {
+    let next_member = AtomicUsize::new(0);
+
+    let mut planned: Vec<(usize, Result<PackagePins>)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.get().min(members.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut planned = Vec::new();
+                    loop {
+                        let index = next_member.fetch_add(1, Ordering::Relaxed);
+                        let Some(member) = members.get(index) else {
+                            break;
+                        };
+                        planned.push((
+                            index,
+                            plan_package_pins(member, resolver_for(index), options),
+                        ));
+                    }
+                    planned
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("Workspace worker panicked"))
+            .collect()
+    });
+
+    planned.sort_by_key(|(index, _)| *index);
+    planned
+        .into_iter()
+        .map(|(_, package_pins)| package_pins)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolver::build_resolver;
+    use crate::test_utils::{fixture_path, parse_fixture_lock, parse_fixture_package};
+    use crate::types::PackageManager;
+
+    #[test]
+    fn find_workspace_members() {
+        let root = fixture_path("npm-workspace");
+        let workspaces = parse_fixture_package("npm-workspace")
+            .workspaces
+            .expect("Expected workspaces");
+
+        assert_eq!(
+            find_members(&root, workspaces.packages()),
+            vec![
+                root.join("packages/a"),
+                root.join("packages/b"),
+                root.join("packages/c"),
+                root.join("tools/cli"),
+            ]
+        );
+        assert!(find_members(&root, &["packages/**", "!packages/a"]).is_empty());
+        assert!(find_members(&root, &["missing/*"]).is_empty());
+    }
+
+    #[test]
+    fn members_pins_do_not_depend_on_thread_count() {
+        let root = fixture_path("npm-workspace");
+        let members = find_members(&root, &["packages/*", "tools/cli"]);
+        let resolver = build_resolver(&parse_fixture_lock("npm-workspace", PackageManager::Npm));
+
+        let pinned = |threads| -> Vec<(String, Vec<(String, String)>)> {
+            plan_members_pins(
+                &members,
+                &resolver,
+                &PinOptions::default(),
+                NonZeroUsize::new(threads).expect("Invalid thread count"),
+            )
+            .expect("Unable to compute dependency versions to pin")
+            .into_iter()
+            .map(|package_pins| {
+                (
+                    package_pins.name,
+                    package_pins
+                        .pin_plan
+                        .versions_to_pin
+                        .into_iter()
+                        .map(|version_to_pin| {
+                            (version_to_pin.dependency, version_to_pin.locked_version)
+                        })
+                        .collect(),
+                )
+            })
+            .collect()
+        };
+
+        let expected = vec![
+            (
+                "a".to_string(),
+                vec![("lodash".to_string(), "4.17.21".to_string())],
+            ),
+            (
+                "b".to_string(),
+                vec![("react".to_string(), "18.2.0".to_string())],
+            ),
+            ("c".to_string(), vec![]),
+            (
+                "cli".to_string(),
+                vec![
+                    ("lodash".to_string(), "4.17.21".to_string()),
+                    ("typescript".to_string(), "5.3.3".to_string()),
+                ],
+            ),
+        ];
+        for threads in [1, 2, 8] {
+            assert_eq!(pinned(threads), expected, "threads = {threads}");
+        }
+    }
+
+    #[test]
+    fn members_resolve_against_their_closest_lock() {
+        let root = fixture_path("mixed-workspace");
+        let members = find_members(&root, &["packages/*"]);
+        let mut resolver_cache = ResolverCache::default();
+
+        let resolvers =
+            load_members_resolvers(&members, FindUpOptions::default(), &mut resolver_cache)
+                .expect("Unable to load members resolvers");
+        let pinned: Vec<(String, String)> = plan_members_pins_with_resolvers(
+            &members,
+            &resolvers,
+            &PinOptions::default(),
+            NonZeroUsize::MIN,
+        )
+        .expect("Unable to compute dependency versions to pin")
+        .into_iter()
+        .flat_map(|package_pins| {
+            package_pins
+                .pin_plan
+                .versions_to_pin
+                .into_iter()
+                .map(move |version_to_pin| {
+                    (package_pins.name.clone(), version_to_pin.locked_version)
+                })
+        })
+        .collect();
+
+        assert_eq!(
+            pinned,
+            vec![
+                ("tool".to_string(), "4.17.20".to_string()),
+                ("web".to_string(), "4.17.21".to_string()),
+            ]
+        );
+        assert_eq!(resolver_cache.loads(), 2);
+    }
+
+    #[test]
+    fn find_workspace_members_from_object_form() {
+        let root = fixture_path("yarn-workspace-object");
+        let workspaces = parse_fixture_package("yarn-workspace-object")
+            .workspaces
+            .expect("Expected workspaces");
+
+        assert_eq!(
+            find_members(&root, workspaces.packages()),
+            vec![root.join("packages/app")]
+        );
+    }
+}
@@ -0,0 +1,36 @@
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::process::{Command, Output};
+
+    fn check_engines(fixture: &str) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_check-engines"))
+            .current_dir(
+                PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                    .join("tests/fixtures")
+                    .join(fixture),
+            )
+            .output()
+            .expect("Unable to run check-engines")
+    }
+
+    #[test]
+    fn reports_violations_and_fails() {
+        let output = check_engines("npm-engines-mismatch");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert_eq!(output.status.code(), Some(1));
+        assert!(stdout.contains("Dependency engines not satisfied by the project"));
+        assert!(stdout.contains("requires node >=18, the project allows >=16"));
+        assert!(!stdout.contains("lodash"));
+    }
+
+    #[test]
+    fn succeeds_when_engines_are_satisfied() {
+        let output = check_engines("npm-v3-engines");
+
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout)
+            .contains("All dependency engines are satisfied"));
+    }
+}
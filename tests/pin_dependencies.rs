@@ -0,0 +1,1141 @@
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::process::{Command, Output, Stdio};
+
+    fn fixture_path(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    fn copy_dir(from: &Path, to: &Path) {
+        std::fs::create_dir_all(to).expect("Unable to create temporary directory");
+
+        for entry in std::fs::read_dir(from).expect("Unable to read fixture") {
+            let path = entry.expect("Unable to read fixture entry").path();
+            let target = to.join(path.file_name().expect("Fixture entry has no name"));
+            if path.is_dir() {
+                copy_dir(&path, &target);
+            } else {
+                std::fs::copy(&path, &target).expect("Unable to copy fixture entry");
+            }
+        }
+    }
+
+    fn copy_fixture(name: &str, test_name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("riri-node-tools-it-{test_name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        copy_dir(&fixture_path(name), &dir);
+
+        dir
+    }
+
+    /// Copy of `npm-sections` whose lock file is older than its package.json, which gets warned about.
+    fn copy_fixture_with_stale_lock(test_name: &str) -> PathBuf {
+        let project = copy_fixture("npm-sections", test_name);
+        let earlier = std::time::SystemTime::now()
+            .checked_sub(std::time::Duration::from_secs(100))
+            .expect("Invalid time");
+        std::fs::File::options()
+            .write(true)
+            .open(project.join("package-lock.json"))
+            .and_then(|file| file.set_modified(earlier))
+            .expect("Unable to set modification time");
+
+        project
+    }
+
+    fn pin_dependencies(cwd: &Path, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_pin-dependencies"))
+            .arg("--cwd")
+            .arg(cwd)
+            .args(args)
+            .output()
+            .expect("Unable to run pin-dependencies")
+    }
+
+    fn pin_dependencies_with_stdin(cwd: &Path, args: &[&str], input: &str) -> Output {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_pin-dependencies"))
+            .arg("--cwd")
+            .arg(cwd)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Unable to run pin-dependencies");
+        // Runs failing before reading stdin close it early.
+        let _ = child
+            .stdin
+            .take()
+            .expect("Missing stdin")
+            .write_all(input.as_bytes());
+        child
+            .wait_with_output()
+            .expect("Unable to run pin-dependencies")
+    }
+
+    fn stdout(output: &Output) -> String {
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout.clone()).expect("Invalid UTF-8 output")
+    }
+
+    /// Progress, warnings, errors and timings, which are written to stderr.
+    fn stderr(output: &Output) -> String {
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    }
+
+    #[test]
+    fn npm_lock_pins_are_listed() {
+        let stdout = stdout(&pin_dependencies(&fixture_path("npm-sections"), &[]));
+
+        assert!(stdout.contains("Dependency versions that can be pinned"));
+        assert!(stdout.contains("lodash:  ^4.17.0  →  4.17.21"));
+        assert!(stdout.contains("typescript:  ~5.3.0  →  5.3.3"));
+    }
+
+    #[test]
+    fn yarn_berry_lock_pins_are_listed() {
+        let stdout = stdout(&pin_dependencies(&fixture_path("yarn-berry-crlf"), &[]));
+
+        assert!(stdout.contains("lodash:  ^4.17.0  →  4.17.21"));
+    }
+
+    #[test]
+    fn pnpm_lock_pins_are_listed() {
+        let stdout = stdout(&pin_dependencies(&fixture_path("pnpm-v6-peer-suffix"), &[]));
+
+        assert!(stdout.contains("react-dom:  ^18.0.0  →  18.2.0"));
+    }
+
+    #[test]
+    fn bun_lock_pins_are_listed() {
+        let stdout = stdout(&pin_dependencies(&fixture_path("bun-text-lock"), &[]));
+
+        assert!(stdout.contains("→  20.10.5"));
+        assert!(stdout.contains("→  4.17.21"));
+        assert!(stdout.contains("→  5.3.3"));
+    }
+
+    #[test]
+    fn lock_files_over_max_lockfile_size_are_refused() {
+        let output = pin_dependencies(
+            &fixture_path("npm-sections"),
+            &["--max-lockfile-size", "100"],
+        );
+
+        assert_eq!(output.status.code(), Some(2));
+        assert!(stderr(&output).contains("over the limit of 100 bytes"));
+    }
+
+    #[test]
+    fn unknown_lockfile_version_is_read_as_the_newest_when_allowed() {
+        let project = fixture_path("npm-unknown-lockfile-version");
+
+        assert_eq!(pin_dependencies(&project, &[]).status.code(), Some(2));
+
+        let output = pin_dependencies(&project, &["--allow-unknown-lockfile-version"]);
+        assert!(stderr(&output).contains("Unsupported lockfile version 99"));
+        assert!(stdout(&output).contains("lodash:  ^4.17.0  →  4.17.21"));
+    }
+
+    #[test]
+    fn pin_style_sets_the_written_specifier() {
+        for (pin_style, specifier) in [
+            ("exact", "4.17.21"),
+            ("tilde", "~4.17.21"),
+            ("caret", "^4.17.21"),
+        ] {
+            let project = copy_fixture("npm-sections", &format!("pin-style-{pin_style}"));
+
+            let stdout = stdout(&pin_dependencies(
+                &project,
+                &["--pin-style", pin_style, "--update"],
+            ));
+            let package_json = std::fs::read_to_string(project.join("package.json"))
+                .expect("Unable to read output");
+
+            assert!(
+                package_json.contains(&format!(r#""lodash": "{specifier}""#)),
+                "{pin_style}"
+            );
+            assert!(stdout.contains(&format!("→  {specifier}")), "{pin_style}");
+        }
+    }
+
+    #[test]
+    fn timings_list_every_executed_step() {
+        let project = copy_fixture("npm-sections", "timings");
+
+        let output = pin_dependencies(&project, &["--timings", "--update"]);
+        assert!(!stdout(&output).contains("[TIMINGS]"));
+        let stderr = stderr(&output);
+        let steps: Vec<&str> = stderr
+            .lines()
+            .filter_map(|line| line.strip_prefix("[TIMINGS] "))
+            .collect();
+
+        for step in [
+            "Resolving package.json",
+            "Resolving lock file",
+            "Parsing package.json",
+            "Parsing lock file",
+            "Computing dependency versions to pin",
+            "Updating package.json",
+            "Total",
+        ] {
+            assert!(steps.iter().any(|line| line.starts_with(step)), "{step}");
+        }
+        assert_eq!(steps.len(), 7);
+    }
+
+    #[test]
+    fn dependencies_in_several_sections_are_reported() {
+        let output = pin_dependencies(&fixture_path("npm-optional-in-prod"), &[]);
+        let stderr = stderr(&output);
+
+        assert!(output.status.success());
+        assert!(stderr.contains(
+            "[WARN] fsevents is declared in dependencies and optionalDependencies, keep it in a single section or pass --dedupe-write."
+        ));
+        assert!(!stderr.contains("[WARN] lodash"));
+    }
+
+    #[test]
+    fn lock_file_and_manifest_are_read_from_stdin() {
+        let project = fixture_path("npm-sections");
+        let read = |name: &str| {
+            std::fs::read_to_string(project.join(name)).expect("Unable to read fixture")
+        };
+
+        let from_lock = stdout(&pin_dependencies_with_stdin(
+            &project,
+            &["--lockfile", "-", "--package-manager", "npm"],
+            &read("package-lock.json"),
+        ));
+        let from_manifest = stdout(&pin_dependencies_with_stdin(
+            &project,
+            &["--manifest", "-"],
+            &read("package.json"),
+        ));
+
+        for stdout in [from_lock, from_manifest] {
+            assert!(stdout.contains("lodash:  ^4.17.0  →  4.17.21"));
+        }
+        assert_eq!(
+            pin_dependencies_with_stdin(&project, &["--lockfile", "-"], "{}")
+                .status
+                .code(),
+            Some(2)
+        );
+        assert_eq!(
+            pin_dependencies_with_stdin(&project, &["--manifest", "-", "-u"], "{}")
+                .status
+                .code(),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn update_writes_pinned_package_json() {
+        let project = copy_fixture("npm-sections", "update");
+
+        let stdout = stdout(&pin_dependencies(&project, &["--update"]));
+        let package_json =
+            std::fs::read_to_string(project.join("package.json")).expect("Unable to read output");
+
+        assert!(stdout.contains("Dependency versions pinned"));
+        assert!(package_json.contains(r#""lodash": "4.17.21""#));
+        assert!(package_json.contains(r#""prettier": "3.1.1""#));
+        assert!(package_json.contains(r#""fsevents": "2.3.3""#));
+    }
+
+    #[test]
+    fn output_is_written_even_when_everything_is_pinned() {
+        let project = copy_fixture("npm-sections", "output-pinned");
+        stdout(&pin_dependencies(&project, &["--update"]));
+        let pinned =
+            std::fs::read_to_string(project.join("package.json")).expect("Unable to read output");
+
+        stdout(&pin_dependencies(
+            &project,
+            &["--update", "--output", "out.json"],
+        ));
+
+        assert_eq!(
+            std::fs::read_to_string(project.join("out.json")).expect("Unable to read output"),
+            pinned
+        );
+    }
+
+    #[test]
+    fn already_pinned_project_reports_nothing_to_do() {
+        let stdout = stdout(&pin_dependencies(&fixture_path("empty-dependencies"), &[]));
+
+        assert!(stdout.contains("All dependency versions are already pinned"));
+    }
+
+    #[test]
+    fn verbosity_controls_what_gets_printed() {
+        let project = fixture_path("npm-sections");
+
+        let progress = pin_dependencies(&project, &["-v"]);
+        assert!(stderr(&progress).contains("[STARTED]"));
+        assert!(stdout(&progress).contains("lodash:  ^4.17.0  →  4.17.21"));
+
+        for args in [&[][..], &["-q"][..]] {
+            let results = pin_dependencies(&project, args);
+            assert!(!stderr(&results).contains("[STARTED]"), "{args:?}");
+            assert!(
+                stdout(&results).contains("lodash:  ^4.17.0  →  4.17.21"),
+                "{args:?}"
+            );
+        }
+
+        let silent = pin_dependencies(&project, &["-qq"]);
+        assert_eq!(stdout(&silent), "");
+        assert_eq!(stderr(&silent), "");
+    }
+
+    #[test]
+    fn piped_output_has_no_escape_codes() {
+        let output = pin_dependencies(&fixture_path("npm-sections"), &["-vv"]);
+
+        assert!(stderr(&output).contains("[SUCCESS]"));
+        assert!(!output.stdout.contains(&0x1b));
+        assert!(!output.stderr.contains(&0x1b));
+    }
+
+    #[test]
+    fn explicit_lockfile_with_package_manager() {
+        let project = copy_fixture("npm-sections", "explicit-lockfile");
+        std::fs::rename(
+            project.join("package-lock.json"),
+            project.join("npm.lock.json"),
+        )
+        .expect("Unable to rename lock file");
+
+        let stdout = stdout(&pin_dependencies(
+            &project,
+            &["--lockfile", "npm.lock.json", "--package-manager", "npm"],
+        ));
+        assert!(stdout.contains("lodash:  ^4.17.0  →  4.17.21"));
+
+        let output = pin_dependencies(&project, &["--lockfile", "npm.lock.json"]);
+        assert!(!output.status.success());
+    }
+
+    #[test]
+    fn explicit_manifest_and_lockfile_bypass_the_finder() {
+        let project = copy_fixture("npm-sections", "explicit-manifest");
+        let elsewhere = project.join("elsewhere");
+        std::fs::create_dir_all(&elsewhere).expect("Unable to create directory");
+        let manifest = project.join("app.package.json");
+        let lockfile = project.join("app.lock.json");
+        std::fs::rename(project.join("package.json"), &manifest)
+            .expect("Unable to rename package.json");
+        std::fs::rename(project.join("package-lock.json"), &lockfile)
+            .expect("Unable to rename lock file");
+
+        let stdout = stdout(&pin_dependencies(
+            &elsewhere,
+            &[
+                "--manifest",
+                manifest.to_str().expect("Invalid manifest path"),
+                "--lockfile",
+                lockfile.to_str().expect("Invalid lock file path"),
+                "--package-manager",
+                "npm",
+            ],
+        ));
+        assert!(stdout.contains("lodash:  ^4.17.0  →  4.17.21"));
+
+        let output = pin_dependencies(
+            &elsewhere,
+            &[
+                "--manifest",
+                project.to_str().expect("Invalid project path"),
+            ],
+        );
+        assert_eq!(output.status.code(), Some(2));
+    }
+
+    #[test]
+    fn since_only_pins_dependencies_changed_since_the_git_ref() {
+        let project = copy_fixture("npm-sections", "since");
+        let output = pin_dependencies(&project, &["--since", "HEAD"]);
+        assert!(stderr(&output).contains("is not in a git repository, --since HEAD is ignored"));
+        assert!(stdout(&output).contains("lodash:  ^4.17.0  →  4.17.21"));
+
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(&project)
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .output()
+                .expect("Unable to run git")
+                .status;
+            assert!(status.success(), "git {args:?} failed");
+        };
+        git(&["init", "--quiet"]);
+        git(&["add", "package.json"]);
+        git(&["commit", "--quiet", "-m", "Initial commit"]);
+        let package_json =
+            std::fs::read_to_string(project.join("package.json")).expect("Unable to read input");
+        std::fs::write(
+            project.join("package.json"),
+            package_json.replace(r#""prettier": "^3.0.0""#, r#""prettier": "^3.1.0""#),
+        )
+        .expect("Unable to write package.json");
+
+        let stdout = stdout(&pin_dependencies(&project, &["--since", "HEAD"]));
+        assert!(stdout.contains("prettier:  ^3.1.0  →  3.1.1"));
+        assert!(!stdout.contains("lodash"));
+        assert!(!stdout.contains("typescript"));
+
+        let output = pin_dependencies(&project, &["--since", "missing-ref"]);
+        assert_eq!(output.status.code(), Some(2));
+        assert!(stderr(&output).contains("Unknown git ref missing-ref"));
+    }
+
+    #[test]
+    fn pnpm_empty_versions_are_skipped_with_a_warning() {
+        let output = pin_dependencies(&fixture_path("pnpm-empty-version"), &[]);
+        let stdout = stdout(&output);
+
+        assert!(stderr(&output).contains(
+            r#"[WARN] Skipping react: its pnpm lock file version "" is not a valid version."#
+        ));
+        assert!(stdout.contains("lodash:  ^4.17.0  →  4.17.21"));
+        assert!(!stdout.contains("react:  ^18.0.0"));
+    }
+
+    #[test]
+    fn warns_when_declared_package_manager_disagrees_with_lock() {
+        let output = pin_dependencies(&fixture_path("npm-package-manager-mismatch"), &[]);
+        let stderr = stderr(&output);
+
+        assert!(stderr.contains("[WARN] package.json declares yarn as its package manager but "));
+        assert!(stderr.contains("package-lock.json belongs to npm"));
+        assert!(stdout(&output).contains("lodash:  ^4.17.0  →  4.17.21"));
+    }
+
+    #[test]
+    fn exit_codes() {
+        let clean = pin_dependencies(&fixture_path("empty-dependencies"), &[]);
+        assert_eq!(clean.status.code(), Some(0));
+
+        let pins_available = pin_dependencies(&fixture_path("npm-sections"), &[]);
+        assert_eq!(pins_available.status.code(), Some(0));
+
+        let missing_package = pin_dependencies(&std::env::temp_dir(), &["--max-depth", "0"]);
+        assert_eq!(missing_package.status.code(), Some(2));
+        assert!(stderr(&missing_package)
+            .contains("Unable to get package.json file in the current directory"));
+
+        let usage_error = pin_dependencies(&fixture_path("npm-sections"), &["--unknown-flag"]);
+        assert_eq!(usage_error.status.code(), Some(2));
+    }
+
+    #[test]
+    fn silent_update_still_writes_package_json() {
+        let project = copy_fixture("npm-sections", "silent-update");
+
+        assert_eq!(
+            stdout(&pin_dependencies(&project, &["-qq", "--update"])),
+            ""
+        );
+        assert!(std::fs::read_to_string(project.join("package.json"))
+            .expect("Unable to read output")
+            .contains(r#""lodash": "4.17.21""#));
+    }
+
+    #[test]
+    fn duplicate_dependency_is_updated_in_every_section() {
+        let project = copy_fixture("npm-duplicate-sections", "duplicate-sections");
+
+        stdout(&pin_dependencies(&project, &["--update"]));
+        let package_json: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(project.join("package.json")).expect("Unable to read output"),
+        )
+        .expect("Invalid package.json output");
+
+        assert_eq!(package_json["dependencies"]["lodash"], "4.17.21");
+        assert_eq!(package_json["devDependencies"]["lodash"], "4.17.21");
+    }
+
+    #[test]
+    fn dedupe_write_keeps_each_dependency_in_a_single_section() {
+        let project = copy_fixture("npm-dedupe-write", "dedupe-write");
+
+        stdout(&pin_dependencies(&project, &["--update", "--dedupe-write"]));
+
+        assert_eq!(
+            std::fs::read_to_string(project.join("package.json")).expect("Unable to read output"),
+            r#"{
+  "name": "npm-dedupe-write",
+  "dependencies": {
+    "lodash": "4.17.21"
+  },
+  "devDependencies": {
+    "typescript": "5.3.3"
+  }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn workspaces_pin_every_member() {
+        for threads in ["1", "4"] {
+            let project = copy_fixture("npm-workspace", &format!("workspaces-{threads}"));
+
+            let stdout = stdout(&pin_dependencies(
+                &project,
+                &["--workspaces", "--threads", threads, "--update"],
+            ));
+            let read_package = |member: &str| -> serde_json::Value {
+                serde_json::from_str(
+                    &std::fs::read_to_string(project.join(member).join("package.json"))
+                        .expect("Unable to read output"),
+                )
+                .expect("Invalid package.json output")
+            };
+
+            assert!(stdout.contains("a (packages/a/package.json)"));
+            assert!(!stdout.contains("c (packages/c/package.json)"));
+            assert_eq!(read_package(".")["devDependencies"]["typescript"], "5.3.3");
+            assert_eq!(
+                read_package("packages/a")["dependencies"]["lodash"],
+                "4.17.21"
+            );
+            assert_eq!(
+                read_package("packages/b")["dependencies"]["react"],
+                "18.2.0"
+            );
+            assert_eq!(
+                read_package("tools/cli")["devDependencies"]["typescript"],
+                "5.3.3"
+            );
+        }
+    }
+
+    #[test]
+    fn warns_when_lock_is_older_than_package_json() {
+        let project = copy_fixture("npm-sections", "stale-lock");
+        let now = std::time::SystemTime::now();
+        let earlier = now
+            .checked_sub(std::time::Duration::from_secs(100))
+            .expect("Invalid time");
+        let set_modified = |file: &str, modified| {
+            std::fs::File::options()
+                .write(true)
+                .open(project.join(file))
+                .and_then(|file| file.set_modified(modified))
+                .expect("Unable to set modification time");
+        };
+
+        set_modified("package.json", now);
+        set_modified("package-lock.json", earlier);
+        assert!(stderr(&pin_dependencies(&project, &[]))
+            .contains("package-lock.json is older than package.json"));
+
+        set_modified("package.json", earlier);
+        set_modified("package-lock.json", now);
+        assert!(!stderr(&pin_dependencies(&project, &[])).contains("is older than package.json"));
+    }
+
+    #[test]
+    fn dedupe_report_lists_direct_dependencies_locked_twice() {
+        let duplicates = stdout(&pin_dependencies(
+            &fixture_path("npm-duplicate-versions"),
+            &["--dedupe-report"],
+        ));
+
+        assert!(duplicates.contains("Direct dependencies locked at several versions"));
+        assert!(duplicates.contains("lodash: 3.10.1, 4.17.21"));
+        assert!(!duplicates.contains("legacy-lib: 1.2.0"));
+        assert!(stdout(&pin_dependencies(
+            &fixture_path("npm-sections"),
+            &["--dedupe-report"]
+        ))
+        .contains("No direct dependency is locked at several versions"));
+    }
+
+    #[test]
+    fn deno_manifest_npm_imports_are_pinned() {
+        let project = copy_fixture("deno-npm-imports", "deno-manifest");
+
+        let stdout = stdout(&pin_dependencies(
+            &project,
+            &["--manifest", "deno.json", "--update"],
+        ));
+        let deno_json =
+            std::fs::read_to_string(project.join("deno.json")).expect("Unable to read output");
+
+        assert!(stdout.contains("→  4.17.21"));
+        assert!(deno_json.contains(r#""lodash": "npm:lodash@4.17.21""#));
+        assert!(deno_json.contains(r#""@types/lodash": "npm:@types/lodash@4.14.202""#));
+        assert!(deno_json.contains(r#""chalk": "npm:chalk@5.3.0""#));
+        assert!(deno_json.contains(r#""@std/path": "jsr:@std/path@^1.0.0""#));
+    }
+
+    #[test]
+    fn print_resolver_dumps_lock_entries() {
+        let output = pin_dependencies(&fixture_path("npm-sections"), &["-q", "--print-resolver"]);
+        let dump: serde_json::Value =
+            serde_json::from_str(&stdout(&output)).expect("Invalid JSON dump");
+
+        assert_eq!(dump["node_modules/lodash"], "4.17.21");
+        assert_eq!(dump["node_modules/typescript"], "5.3.3");
+    }
+
+    #[test]
+    fn print_resolver_dump_stays_parseable_with_a_stale_lock() {
+        let project = copy_fixture_with_stale_lock("print-resolver-stale-lock");
+
+        let output = pin_dependencies(&project, &["--print-resolver"]);
+        let dump: serde_json::Value =
+            serde_json::from_str(&stdout(&output)).expect("Invalid JSON dump");
+
+        assert_eq!(dump["node_modules/lodash"], "4.17.21");
+        assert!(stderr(&output).contains("package-lock.json is older than package.json"));
+    }
+
+    #[test]
+    fn package_json_and_package_lock_env_vars_replace_the_lookup() {
+        let fixture = fixture_path("npm-sections");
+        let empty = std::env::temp_dir().join("riri-node-tools-it-env-paths");
+        let _ = std::fs::remove_dir_all(&empty);
+        std::fs::create_dir_all(&empty).expect("Unable to create temporary directory");
+
+        let output = Command::new(env!("CARGO_BIN_EXE_pin-dependencies"))
+            .arg("--cwd")
+            .arg(&empty)
+            .env("PACKAGE_JSON", fixture.join("package.json"))
+            .env("PACKAGE_LOCK", fixture.join("missing-lock.json"))
+            .arg("--lockfile")
+            .arg(fixture.join("package-lock.json"))
+            .output()
+            .expect("Unable to run pin-dependencies");
+
+        assert!(stdout(&output).contains("lodash:  ^4.17.0  →  4.17.21"));
+
+        let output = Command::new(env!("CARGO_BIN_EXE_pin-dependencies"))
+            .arg("--cwd")
+            .arg(&empty)
+            .env("PACKAGE_JSON", fixture.join("package.json"))
+            .env("PACKAGE_LOCK", fixture.join("package-lock.json"))
+            .output()
+            .expect("Unable to run pin-dependencies");
+
+        assert!(stdout(&output).contains("lodash:  ^4.17.0  →  4.17.21"));
+    }
+
+    #[test]
+    fn package_json_env_var_is_ignored_with_workspaces() {
+        let output = Command::new(env!("CARGO_BIN_EXE_pin-dependencies"))
+            .arg("--cwd")
+            .arg(fixture_path("npm-workspace"))
+            .arg("--workspaces")
+            .env(
+                "PACKAGE_JSON",
+                fixture_path("npm-sections").join("package.json"),
+            )
+            .output()
+            .expect("Unable to run pin-dependencies");
+
+        assert!(!stdout(&output).contains("npm-sections"));
+    }
+
+    #[test]
+    fn ignore_file_patterns_merge_with_ignore_flags() {
+        let ignore_file = fixture_path("ignore-file").join("pin-ignore");
+        let output = pin_dependencies(
+            &fixture_path("npm-sections"),
+            &[
+                "--ignore-file",
+                ignore_file.to_str().expect("Invalid fixture path"),
+                "--ignore",
+                "fsevents",
+            ],
+        );
+        let stdout = stdout(&output);
+
+        assert!(stdout.contains("lodash:  ^4.17.0  →  4.17.21"));
+        assert!(!stdout.contains("prettier:"));
+        assert!(!stdout.contains("typescript:"));
+        assert!(!stdout.contains("fsevents:"));
+    }
+
+    #[test]
+    fn yarn_package_locked_at_two_majors_pins_the_declared_one() {
+        let output = stdout(&pin_dependencies(&fixture_path("yarn-berry-majors"), &[]));
+
+        assert!(output.contains("lodash:  ^3.10.0  →  3.10.1"));
+    }
+
+    #[test]
+    fn dump_unresolved_lists_dependencies_missing_from_the_lock() {
+        let output = pin_dependencies(&fixture_path("npm-stale-lock"), &["--dump-unresolved"]);
+
+        assert_eq!(stdout(&output), "chalk\nleft-pad\n");
+    }
+
+    #[test]
+    fn config_print_merges_npdrc_and_cli_flags() {
+        let project = copy_fixture("npm-sections", "config-print");
+        std::fs::write(
+            project.join(".npdrc"),
+            r#"{ "update": true, "pinStyle": "tilde", "prod": true, "dedupeWrite": false }"#,
+        )
+        .expect("Unable to write .npdrc");
+
+        let config_print = |args: &[&str]| -> serde_json::Value {
+            let output = pin_dependencies(&project, &[&["-q", "--config-print"], args].concat());
+            serde_json::from_str(&stdout(&output)).expect("Invalid JSON config")
+        };
+
+        let config = config_print(&[]);
+        assert_eq!(config["update"], true);
+        assert_eq!(config["pinStyle"], "tilde");
+        assert_eq!(config["prod"], true);
+        assert_eq!(config["dedupeWrite"], false);
+        assert_eq!(config["format"], "table");
+
+        let config = config_print(&["--no-update", "--pin-style", "caret", "--dedupe-write"]);
+        assert_eq!(config["update"], false);
+        assert_eq!(config["pinStyle"], "caret");
+        assert_eq!(config["prod"], true);
+        assert_eq!(config["dedupeWrite"], true);
+        assert_eq!(
+            std::fs::read_to_string(project.join("package.json")).expect("Unable to read output"),
+            std::fs::read_to_string(fixture_path("npm-sections").join("package.json"))
+                .expect("Unable to read fixture")
+        );
+    }
+
+    #[test]
+    fn npdrc_with_unknown_keys_is_an_error() {
+        let project = copy_fixture("npm-sections", "npdrc-unknown-key");
+        std::fs::write(project.join(".npdrc"), r#"{ "pin-style": "tilde" }"#)
+            .expect("Unable to write .npdrc");
+
+        let output = pin_dependencies(&project, &[]);
+
+        assert_eq!(output.status.code(), Some(2));
+        assert!(stderr(&output).contains("unknown field `pin-style`"));
+    }
+
+    #[test]
+    fn update_indents_minified_package_with_two_spaces() {
+        let project = copy_fixture("npm-minified", "minified");
+
+        stdout(&pin_dependencies(&project, &["--update"]));
+
+        assert_eq!(
+            std::fs::read_to_string(project.join("package.json")).expect("Unable to read output"),
+            "{\n  \"name\": \"npm-minified\",\n  \"version\": \"1.0.0\",\n  \"dependencies\": {\n    \"lodash\": \"4.17.21\"\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn explain_traces_a_single_dependency() {
+        let project = fixture_path("npm-unresolved-dependency");
+
+        let output = stdout(&pin_dependencies(&project, &["--explain", "lodash"]));
+        assert!(output.contains("lodash in dependencies is declared as ^4.17.0"));
+        assert!(output.contains("is looked up as node_modules/lodash, locked at 4.17.21"));
+        assert!(output.contains("lodash in dependencies: pin"));
+
+        let output = stdout(&pin_dependencies(&project, &["--explain", "left-pad"]));
+        assert!(
+            output.contains("is looked up as node_modules/left-pad, not found in the lock file")
+        );
+        assert!(output.contains("left-pad in dependencies: unresolved in the lock file"));
+
+        let explanation: serde_json::Value = serde_json::from_str(&stdout(&pin_dependencies(
+            &project,
+            &["-q", "--explain", "left-pad", "--format", "json"],
+        )))
+        .expect("Invalid JSON explanation");
+        assert_eq!(explanation[0]["decision"]["kind"], "unresolved");
+        assert_eq!(explanation[0]["lockedVersion"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn update_keeps_tab_indentation() {
+        let project = copy_fixture("npm-tab-indent", "tab-indent");
+
+        stdout(&pin_dependencies(&project, &["--update"]));
+
+        assert_eq!(
+            std::fs::read_to_string(project.join("package.json")).expect("Unable to read output"),
+            "{\n\t\"name\": \"npm-tab-indent\",\n\t\"dependencies\": {\n\t\t\"lodash\": \"4.17.21\"\n\t}\n}\n"
+        );
+    }
+
+    #[test]
+    fn pin_subcommand_pins_like_the_top_level_command() {
+        let project = copy_fixture("npm-sections", "pin-subcommand");
+
+        let tilde = stdout(&pin_dependencies(
+            &project,
+            &["pin", "--pin-style", "tilde"],
+        ));
+        assert!(tilde.contains("lodash:  ^4.17.0  →  ~4.17.21"));
+
+        stdout(&pin_dependencies(&project, &["pin", "-q", "--update"]));
+        assert!(std::fs::read_to_string(project.join("package.json"))
+            .expect("Unable to read output")
+            .contains(r#""lodash": "4.17.21""#));
+    }
+
+    #[test]
+    fn check_subcommand_fails_while_versions_are_left_to_pin() {
+        let project = copy_fixture("npm-sections", "check-subcommand");
+
+        let output = pin_dependencies(&project, &["check"]);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stdout).contains("lodash:  ^4.17.0  →  4.17.21"));
+        assert_eq!(
+            pin_dependencies(&project, &["check", "--update"])
+                .status
+                .code(),
+            Some(2)
+        );
+
+        stdout(&pin_dependencies(&project, &["pin", "--update"]));
+        assert!(stdout(&pin_dependencies(&project, &["check"]))
+            .contains("All dependency versions are already pinned"));
+    }
+
+    #[test]
+    fn subcommands_accept_manifest() {
+        let project = fixture_path("npm-sections");
+        let manifest = project.join("package.json");
+        let manifest = manifest.to_str().expect("Invalid fixture path");
+
+        let output = pin_dependencies(&project, &["check", "--manifest", manifest]);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stdout).contains("lodash:  ^4.17.0  →  4.17.21"));
+        assert!(stdout(&pin_dependencies(
+            &project,
+            &["pin", "--manifest", manifest]
+        ))
+        .contains("lodash:  ^4.17.0  →  4.17.21"));
+    }
+
+    #[test]
+    fn engines_subcommand_checks_engines() {
+        let stdout = stdout(&pin_dependencies(
+            &fixture_path("npm-engines-mismatch"),
+            &["engines"],
+        ));
+
+        assert!(stdout.contains("modern-lib requires node >=18, the project allows >=16"));
+        assert!(!stdout.contains("Dependency versions that can be pinned"));
+    }
+
+    #[test]
+    fn check_engines_reports_violations_instead_of_pins() {
+        let stdout = stdout(&pin_dependencies(
+            &fixture_path("npm-engines-mismatch"),
+            &["--check-engines"],
+        ));
+
+        assert!(stdout.contains("modern-lib requires node >=18, the project allows >=16"));
+        assert!(!stdout.contains("lodash requires"));
+        assert!(!stdout.contains("Dependency versions that can be pinned"));
+    }
+
+    #[test]
+    fn check_engines_fails_on_violations_under_engine_strict() {
+        let project = copy_fixture("npm-engine-strict", "engine-strict");
+
+        let output = pin_dependencies(&project, &["--check-engines"]);
+        assert_eq!(output.status.code(), Some(1));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("modern-lib requires node >=18, the project allows >=16"));
+        assert!(stderr(&output)
+            .contains("Found 1 dependency engine violation while .npmrc sets engine-strict"));
+
+        std::fs::write(project.join(".npmrc"), "engine-strict=false\n")
+            .expect("Unable to write .npmrc");
+        let output = pin_dependencies(&project, &["--check-engines"]);
+        assert_eq!(output.status.code(), Some(0));
+    }
+
+    #[test]
+    fn check_engines_json_report() {
+        let output = pin_dependencies(
+            &fixture_path("npm-engines-mismatch"),
+            &["-q", "--check-engines", "--format", "json"],
+        );
+        let report: serde_json::Value =
+            serde_json::from_str(&stdout(&output)).expect("Invalid JSON report");
+
+        assert_eq!(
+            report,
+            serde_json::json!([
+                {
+                    "dependency": "lodash",
+                    "field": "node",
+                    "required": ">=4",
+                    "project": ">=16",
+                    "satisfied": true
+                },
+                {
+                    "dependency": "modern-lib",
+                    "field": "node",
+                    "required": ">=18",
+                    "project": ">=16",
+                    "satisfied": false
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn check_engines_ndjson_report() {
+        let output = pin_dependencies(
+            &fixture_path("npm-engines-mismatch"),
+            &["-q", "--check-engines", "--format", "ndjson"],
+        );
+        let checks: Vec<serde_json::Value> = stdout(&output)
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("Invalid JSON line"))
+            .collect();
+
+        assert_eq!(checks.len(), 2);
+        assert_eq!(checks[1]["dependency"], "modern-lib");
+        assert_eq!(checks[1]["satisfied"], false);
+    }
+
+    #[test]
+    fn check_engines_falls_back_to_node_version_files() {
+        for (name, project) in [
+            ("nvmrc", "16.20.2"),
+            ("node-version", "18"),
+            ("tool-versions", "18.19.0"),
+        ] {
+            let stdout = stdout(&pin_dependencies(&fixture_path(name), &["--check-engines"]));
+
+            assert!(
+                stdout.contains(&format!(
+                    "modern-lib requires node >=20, the project allows {project}"
+                )),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn downgrades_need_allow_downgrade() {
+        let project = copy_fixture("npm-downgrade", "downgrade");
+        let package_json = || {
+            std::fs::read_to_string(project.join("package.json")).expect("Unable to read output")
+        };
+
+        let skipped = stderr(&pin_dependencies(&project, &["--update"]));
+        assert!(skipped.contains(
+            "Skipping legacy-lib in dependencies: 1.9.0 is older than ^2.0.0, pass --allow-downgrade"
+        ));
+        assert!(package_json().contains(r#""legacy-lib": "^2.0.0""#));
+        assert!(package_json().contains(r#""lodash": "4.17.21""#));
+
+        stdout(&pin_dependencies(
+            &project,
+            &["--update", "--allow-downgrade"],
+        ));
+        assert!(package_json().contains(r#""legacy-lib": "1.9.0""#));
+    }
+
+    #[test]
+    fn dev_scope_limits_pinned_dev_dependencies() {
+        let stdout = stdout(&pin_dependencies(
+            &fixture_path("npm-dev-scopes"),
+            &["--dev-scope", "@types/*"],
+        ));
+
+        assert!(stdout.contains("@types/node:"));
+        assert!(stdout.contains("lodash:"));
+        assert!(!stdout.contains("typescript:"));
+        assert!(stdout.contains("--dev-scope '@types/*' -u"));
+    }
+
+    #[test]
+    fn report_file_matches_results() {
+        let project = copy_fixture("npm-sections", "report-file");
+        let report_file = project.join("report.json");
+
+        let stdout = stdout(&pin_dependencies(
+            &project,
+            &["--report-file", "report.json"],
+        ));
+        let report: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(report_file).expect("Unable to read report"),
+        )
+        .expect("Invalid JSON report");
+
+        let versions_to_pin = report[0]["versionsToPin"]
+            .as_array()
+            .expect("Expected versions to pin");
+        assert_eq!(versions_to_pin.len(), 4);
+        assert_eq!(report[0]["stats"]["total"], 4);
+        assert_eq!(report[0]["stats"]["perSection"]["devDependencies"], 2);
+        for version_to_pin in versions_to_pin {
+            let field = |name: &str| {
+                version_to_pin[name]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            assert!(stdout.lines().any(|line| {
+                line.contains(&format!("{}:", field("dependency")))
+                    && line.contains(&field("packageVersion"))
+                    && line.ends_with(&field("lockedVersion"))
+            }));
+        }
+        assert_eq!(versions_to_pin[0]["section"], "dependencies");
+    }
+
+    #[test]
+    fn format_json_prints_the_report() {
+        let output = pin_dependencies(&fixture_path("npm-sections"), &["-q", "--format", "json"]);
+        let report: serde_json::Value =
+            serde_json::from_str(&stdout(&output)).expect("Invalid JSON output");
+
+        assert_eq!(report.as_array().map(Vec::len), Some(1));
+        assert_eq!(report[0]["name"], "npm-sections");
+        assert_eq!(
+            report[0]["lockfileVersion"],
+            serde_json::json!({ "packageManager": "npm", "version": 3 })
+        );
+        assert_eq!(report[0]["stats"]["total"], 4);
+        assert_eq!(report[0]["versionsToPin"].as_array().map(Vec::len), Some(4));
+    }
+
+    #[test]
+    fn json_formats_stay_parseable_when_warnings_are_logged() {
+        let project = copy_fixture_with_stale_lock("json-stale-lock");
+
+        let output = pin_dependencies(&project, &["-v", "--format", "json", "--timings"]);
+        let report: serde_json::Value =
+            serde_json::from_str(&stdout(&output)).expect("Invalid JSON output");
+        assert_eq!(report[0]["name"], "npm-sections");
+        assert!(stderr(&output).contains("package-lock.json is older than package.json"));
+        assert!(stderr(&output).contains("[TIMINGS] Total"));
+
+        let output = pin_dependencies(&project, &["-v", "--format", "ndjson"]);
+        for line in stdout(&output).lines() {
+            serde_json::from_str::<serde_json::Value>(line).expect("Invalid JSON line");
+        }
+        assert!(stderr(&output).contains("package-lock.json is older than package.json"));
+    }
+
+    #[test]
+    fn json_formats_imply_quiet() {
+        let project = copy_fixture_with_stale_lock("json-implies-quiet");
+
+        for format in ["json", "ndjson"] {
+            let output = pin_dependencies(&project, &["--format", format]);
+            assert!(!stdout(&output).is_empty(), "{format}");
+            assert_eq!(stderr(&output), "", "{format}");
+        }
+        let output = pin_dependencies(&project, &["--format", "table"]);
+        assert!(stderr(&output).contains("package-lock.json is older than package.json"));
+
+        std::fs::write(project.join(".npdrc"), r#"{ "format": "json" }"#)
+            .expect("Unable to write .npdrc");
+        assert_eq!(stderr(&pin_dependencies(&project, &[])), "");
+    }
+
+    #[test]
+    fn format_ndjson_prints_one_version_to_pin_per_line() {
+        let output = pin_dependencies(&fixture_path("npm-sections"), &["-q", "--format", "ndjson"]);
+        let versions_to_pin: Vec<serde_json::Value> = stdout(&output)
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("Invalid JSON line"))
+            .collect();
+
+        assert_eq!(versions_to_pin.len(), 4);
+        for version_to_pin in &versions_to_pin {
+            assert_eq!(version_to_pin["package"], "npm-sections");
+            assert!(version_to_pin["dependency"].is_string());
+            assert!(version_to_pin["lockedVersion"].is_string());
+        }
+    }
+
+    #[test]
+    fn format_table_is_the_default() {
+        let project = fixture_path("npm-sections");
+
+        assert_eq!(
+            stdout(&pin_dependencies(&project, &["-q", "--format", "table"])),
+            stdout(&pin_dependencies(&project, &["-q"]))
+        );
+        assert!(stdout(&pin_dependencies(&project, &["-q"]))
+            .contains("Dependency versions that can be pinned"));
+    }
+
+    #[test]
+    fn member_locks_resolve_each_member_against_its_own_lock() {
+        let root = fixture_path("mixed-workspace");
+
+        let shared = stdout(&pin_dependencies(&root, &["--workspaces"]));
+        assert!(!shared.contains("4.17.20"));
+
+        let per_member = stdout(&pin_dependencies(
+            &root,
+            &["--workspaces", "--member-locks"],
+        ));
+        assert!(per_member.contains("→  4.17.20"));
+        assert!(per_member.contains("→  4.17.21"));
+        assert!(per_member.contains("--workspaces --member-locks -u"));
+    }
+
+    #[test]
+    fn yarn_berry_workspace_members_resolve_against_the_root_lock() {
+        let project = copy_fixture("yarn-berry-workspace", "yarn-berry-workspace");
+
+        let output = pin_dependencies(&project, &["--workspaces", "--update"]);
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("0.0.0-use.local"));
+        stdout(&output);
+        let read_package = |member: &str| -> serde_json::Value {
+            serde_json::from_str(
+                &std::fs::read_to_string(project.join(member).join("package.json"))
+                    .expect("Unable to read output"),
+            )
+            .expect("Invalid package.json output")
+        };
+
+        assert_eq!(read_package(".")["devDependencies"]["typescript"], "5.3.3");
+        assert_eq!(
+            read_package("packages/a")["dependencies"]["lodash"],
+            "4.17.21"
+        );
+        assert_eq!(
+            read_package("packages/a")["dependencies"]["@acme/b"],
+            "workspace:^"
+        );
+        assert_eq!(
+            read_package("packages/b")["dependencies"]["@acme/a"],
+            "^1.0.0"
+        );
+        assert_eq!(
+            read_package("packages/b")["devDependencies"]["typescript"],
+            "5.3.3"
+        );
+    }
+
+    #[test]
+    fn empty_lock_is_reported() {
+        let output = pin_dependencies(&fixture_path("yarn-empty-lock"), &[]);
+
+        assert!(stderr(&output).contains("yarn.lock is empty, run yarn install"));
+    }
+}